@@ -0,0 +1,16 @@
+use std::time::{Duration, Instant};
+
+/// Run `f` `iterations` times and print the average time per iteration
+pub fn bench<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+    // warm up caches and the allocator before timing
+    std::hint::black_box(f());
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        std::hint::black_box(f());
+    }
+    let elapsed = start.elapsed() / iterations;
+
+    println!("{:<40} {:?} per iteration", name, elapsed);
+    elapsed
+}
@@ -0,0 +1,83 @@
+//! Compares the compressed size of an archive with each [`DataOrder`], and how long compressing
+//! it takes. The archive has no names, so every file shares a hash and the data order decides the
+//! whole layout:
+//!
+//! ```text
+//! cargo bench --bench data_order
+//! ```
+use sarc::{SarcFile, SarcEntry, Endian, Alignment, Compression, compress};
+use sarc::writer::{DataOrder, WriteOptions};
+
+mod common;
+
+const FILE_COUNT: usize = 32;
+const SMALL_SIZE: usize = 0x400;
+const LARGE_SIZE: usize = 0x1400;
+const ITERATIONS: u32 = 5;
+
+/// An archive alternating small files that are near-copies of each other with larger
+/// incompressible ones, so that in insertion order the small files are further apart than yaz0's
+/// 4 KiB window
+fn make_interleaved_archive() -> SarcFile {
+    let mut seed = 0x1234_5678u32;
+    let mut noise = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+    let template: Vec<u8> = (0..SMALL_SIZE).map(|_| noise()).collect();
+
+    let files = (0..FILE_COUNT).map(|i| {
+        let data = if i % 2 == 0 {
+            let mut data = template.clone();
+            data[0] = i as u8;
+            data
+        } else {
+            (0..LARGE_SIZE).map(|_| noise()).collect()
+        };
+        SarcEntry { name: None, raw_name: None, hash: Some(0), alignment: None, data }
+    });
+
+    SarcFile::from_entries(Endian::Little, files)
+}
+
+fn main() {
+    let sarc = make_interleaved_archive();
+
+    let orders = [
+        ("name", DataOrder::Name),
+        ("files", DataOrder::Files),
+        ("size", DataOrder::Size),
+    ];
+    for &(label, order) in &orders {
+        // minimal padding keeps the files next to each other, rather than 8 KiB apart
+        let options = WriteOptions::new()
+            .data_order(order)
+            .data_alignment(Alignment::MIN_DATA)
+            .minimal_padding(true);
+        let mut data = vec![];
+        sarc.write_with_options(&mut data, &options).unwrap();
+
+        let compressions = [("zstd", Compression::Zstd), ("yaz0", Compression::Yaz0)];
+        for &(compression_label, compression) in &compressions {
+            let compressed = match compress(&data, compression) {
+                Ok(compressed) => compressed,
+                // the feature for this compression isn't enabled
+                Err(_) => continue,
+            };
+            common::bench(
+                &format!("{} ({} order)", compression_label, label),
+                if compression == Compression::Yaz0 { 1 } else { ITERATIONS },
+                || compress(&data, compression).unwrap()
+            );
+            println!(
+                "{:<40} {:#x} -> {:#x} bytes ({:.3})",
+                "",
+                data.len(),
+                compressed.len(),
+                compressed.len() as f64 / data.len() as f64
+            );
+        }
+    }
+}
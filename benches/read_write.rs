@@ -0,0 +1,51 @@
+//! Benchmarks for parsing, writing and compressing archives of a few representative sizes:
+//!
+//! ```text
+//! cargo bench --bench read_write
+//! ```
+use sarc::SarcFile;
+use sarc::testing::make_archive;
+
+mod common;
+
+/// (number of files, size of each file)
+const SIZES: &[(usize, usize)] = &[
+    (10, 0x100),
+    (100, 0x1000),
+    (1000, 0x4000),
+];
+
+fn main() {
+    for &(num_files, file_size) in SIZES {
+        let sarc = make_archive(num_files, file_size);
+        let data = sarc.write_to_vec().unwrap();
+        let label = format!("{} x {:#x}", num_files, file_size);
+        let iterations = if num_files >= 1000 { 5 } else { 50 };
+
+        common::bench(&format!("read ({})", label), iterations, || SarcFile::read(&data).unwrap());
+        common::bench(&format!("write ({})", label), iterations, || sarc.write_to_vec().unwrap());
+
+        #[cfg(feature = "zstd_sarc")] {
+            let zstd = sarc.write_zstd_to_vec().unwrap();
+            common::bench(&format!("write zstd ({})", label), iterations, || {
+                sarc.write_zstd_to_vec().unwrap()
+            });
+            common::bench(&format!("read zstd ({})", label), iterations, || {
+                SarcFile::read(&zstd).unwrap()
+            });
+        }
+
+        // yaz0 compression is slow enough that only the smaller archives are worth timing
+        #[cfg(feature = "yaz0_sarc")] {
+            if num_files < 1000 {
+                let yaz0 = sarc.write_yaz0_to_vec().unwrap();
+                common::bench(&format!("write yaz0 ({})", label), 1, || {
+                    sarc.write_yaz0_to_vec().unwrap()
+                });
+                common::bench(&format!("read yaz0 ({})", label), iterations, || {
+                    SarcFile::read(&yaz0).unwrap()
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use sarc::SarcFile;
+
+fuzz_target!(|data: &[u8]| {
+    // any input is allowed to fail to parse, it just must never panic
+    let _ = SarcFile::try_read(data);
+});
@@ -0,0 +1,66 @@
+use super::{SarcFile, SarcEntry, Endian, Platform, Alignment, SARC_VERSION, HASH_KEY};
+use alloc::{string::String, vec, vec::Vec};
+
+/// A builder for assembling a [`SarcFile`](SarcFile) one file at a time
+///
+/// ```rust
+/// use sarc::{Platform, builder::SarcBuilder};
+///
+/// let sarc = SarcBuilder::for_platform(Platform::Switch)
+///     .file("Actor/foo.txt", b"foo".to_vec())
+///     .file("Actor/bar.txt", b"bar".to_vec())
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct SarcBuilder {
+    byte_order: Endian,
+    data_alignment: Alignment,
+    files: Vec<SarcEntry>,
+}
+
+/// A builder for an empty little endian archive, matching [`SarcFile::default`](SarcFile::default)
+impl Default for SarcBuilder {
+    fn default() -> Self {
+        Self::new(Endian::Little)
+    }
+}
+
+impl SarcBuilder {
+    /// Create a builder for an archive with the given byte order
+    pub fn new(byte_order: Endian) -> Self {
+        Self {
+            byte_order,
+            data_alignment: Alignment::DATA,
+            files: vec![]
+        }
+    }
+
+    /// Create a builder for an archive with the byte order and data section alignment the given
+    /// platform expects. See [`Endian::for_platform`](Endian::for_platform) and
+    /// [`Alignment::data_offset_for_platform`](Alignment::data_offset_for_platform).
+    pub fn for_platform(platform: Platform) -> Self {
+        Self {
+            data_alignment: Alignment::data_offset_for_platform(platform),
+            ..Self::new(Endian::for_platform(platform))
+        }
+    }
+
+    /// Add a file to the archive
+    pub fn file<N: Into<String>, D: Into<Vec<u8>>>(mut self, name: N, data: D) -> Self {
+        self.files.push(SarcEntry::new(name, data));
+        self
+    }
+
+    /// Build the archive
+    pub fn build(self) -> SarcFile {
+        SarcFile {
+            byte_order: self.byte_order,
+            version: SARC_VERSION,
+            hash_key: HASH_KEY,
+            data_alignment: self.data_alignment,
+            data_offset: None,
+            file_size: None,
+            files: self.files
+        }
+    }
+}
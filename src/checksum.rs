@@ -0,0 +1,42 @@
+//! CRC32 checksums of archive entries for integrity checks. Requires the `checksum` feature.
+use super::{SarcFile, SarcEntry};
+
+/// Lookup table for the reflected CRC32 (IEEE 802.3) polynomial
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC32 (IEEE 802.3, the same as zlib/PNG/zip use) of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+impl SarcEntry {
+    /// Compute the CRC32 (IEEE 802.3) checksum of the file's data
+    pub fn checksum(&self) -> u32 {
+        crc32(&self.data)
+    }
+}
+
+impl SarcFile {
+    /// Compute the CRC32 (IEEE 802.3) checksum of every file in the archive, in the same order
+    /// as `files`. Checksums are matched to files by index rather than by name, so nameless files
+    /// and files with names that aren't valid UTF-8 each keep their own.
+    pub fn checksums(&self) -> Vec<u32> {
+        self.files.iter().map(SarcEntry::checksum).collect()
+    }
+}
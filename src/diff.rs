@@ -0,0 +1,41 @@
+use super::{SarcFile, SarcEntry};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// The differences between two archives, by filename. Nameless files are not compared.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SarcDiff<'a> {
+    /// Names of files only present in the new archive
+    pub added: Vec<&'a str>,
+    /// Names of files only present in the original archive
+    pub removed: Vec<&'a str>,
+    /// Names of files present in both archives, but with different data
+    pub changed: Vec<&'a str>,
+}
+
+fn by_name(sarc: &SarcFile) -> BTreeMap<&str, &SarcEntry> {
+    sarc.files
+        .iter()
+        .filter_map(|file| Some((file.name.as_deref()?, file)))
+        .collect()
+}
+
+impl SarcFile {
+    /// Compare this archive against `other`, treating `self` as the original and `other` as the
+    /// new archive. The names in each list are in the order they appear in their archive.
+    pub fn diff<'a>(&'a self, other: &'a SarcFile) -> SarcDiff<'a> {
+        let old = by_name(self);
+        let new = by_name(other);
+
+        SarcDiff {
+            added: other.names().filter(|name| !old.contains_key(name)).collect(),
+            removed: self.names().filter(|name| !new.contains_key(name)).collect(),
+            changed: self.names()
+                .filter(|name| {
+                    new.get(name)
+                        .map(|file| file.data != old[name].data)
+                        .unwrap_or(false)
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use super::{SarcFile, SarcEntry, Endian, Alignment, SARC_VERSION, HASH_KEY};
+use std::path::Path;
+
+impl SarcFile {
+    /// Create an archive from every file in a directory and its subdirectories, named by their
+    /// path relative to `dir`. Path separators are normalized to `/`, the separator SARC names
+    /// use, so archives packed on Windows don't end up with `\` in their names. Files are sorted
+    /// by name.
+    pub fn from_dir<P: AsRef<Path>>(dir: P, byte_order: Endian) -> std::io::Result<Self> {
+        Self::from_dir_with(dir, byte_order, |name| name)
+    }
+
+    /// Create an archive from every file in a directory and its subdirectories, like
+    /// [`from_dir`](SarcFile::from_dir), passing each name through `normalize` after separators
+    /// have been normalized. For example, use `|name| name.to_lowercase()` to lowercase every name.
+    pub fn from_dir_with<P, F>(dir: P, byte_order: Endian, mut normalize: F) -> std::io::Result<Self>
+        where P: AsRef<Path>,
+              F: FnMut(String) -> String,
+    {
+        let mut files = vec![];
+        add_dir(dir.as_ref(), "", &mut files)?;
+        for file in &mut files {
+            let name = file.name.take().map(|name| normalize(normalize_separators(&name)));
+            file.name = name;
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(SarcFile {
+            byte_order,
+            version: SARC_VERSION,
+            hash_key: HASH_KEY,
+            data_alignment: Alignment::DATA,
+            data_offset: None,
+            file_size: None,
+            files
+        })
+    }
+}
+
+/// Recursively add every file in `dir` to `files`, with names prefixed by `prefix`
+fn add_dir(dir: &Path, prefix: &str, files: &mut Vec<SarcEntry>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            add_dir(&entry.path(), &format!("{}/", name), files)?;
+        } else {
+            files.push(SarcEntry::new(name, std::fs::read(entry.path())?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace every `\` in a name with `/`
+pub(crate) fn normalize_separators(name: &str) -> String {
+    name.replace('\\', "/")
+}
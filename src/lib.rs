@@ -1,6 +1,9 @@
 //! A simple to use library for reading/writing SARC and SZS (yaz0 compressed SARCs) in Rust.
 //! 
-//! ```rust
+//! ```rust,no_run
+//! # #[cfg(feature = "std")] {
+//! use sarc::prelude::*;
+//!
 //! // yaz0 and non-yaz0 sarcs can be read the same way
 //! let sarc = SarcFile::read_from_file("Animal_Fish_A.sbactorpack").unwrap();
 //! 
@@ -11,54 +14,707 @@
 //! 
 //! // write as yaz0 compressed sarc
 //! sarc.write_to_compressed_file("animal_test.sarc").unwrap();
+//! # }
 //! ```
 //!
 //! ### Features
 //!
+//! `std` - support writing sarc files, and reading/writing them directly from/to the filesystem
+//! (enabled by default). Without it the crate is `#![no_std]` and only needs `alloc`. It can then
+//! read uncompressed archives from byte slices, either copying them with
+//! [`SarcFile::read`](SarcFile::read) or borrowing from them with
+//! [`SarcFile::read_entries`](SarcFile::read_entries), but can't write archives.
 //! `yaz0_sarc` - support reading/writing yaz0-compressed sarc files
 //! `zstd_sarc` - support reading/writing yaz0-compressed sarc files
+//! `mmap` - support reading sarc files in place through a memory mapping
+//! `checksum` - support computing CRC32 checksums of files
+//! `wasm` - [`wasm-bindgen`](https://docs.rs/wasm-bindgen) bindings for listing, extracting and
+//! repacking archives from JavaScript
+//! `tracing` - emit [`tracing`](https://docs.rs/tracing) events while parsing and writing, for
+//! debugging unusual archives
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, vec, vec::Vec};
+
+#[macro_use]
+mod trace;
 pub mod parser;
+#[cfg(feature = "std")]
 pub mod writer;
+pub mod diff;
+pub mod builder;
+pub mod merge;
+pub mod stats;
+#[cfg(feature = "std")]
+mod dir;
+pub mod prelude;
+#[cfg(feature = "checksum")]
+pub mod checksum;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[doc(hidden)]
+pub mod testing;
+
+pub use parser::{decompress, read_bytes};
+#[cfg(feature = "std")]
+pub use writer::{compress, write_bytes};
 
 /// An in-memory representation of a Sarc archive
 #[derive(Debug)]
 pub struct SarcFile {
     pub byte_order: Endian,
+    /// Version of the SARC format, currently always [`SARC_VERSION`](SARC_VERSION)
+    pub version: u16,
+    /// Key the SFAT hashes were made with, see [`hash_key`](SarcFile::hash_key)
+    hash_key: u32,
+    /// Alignment of the data section when written, see [`data_alignment`](SarcFile::data_alignment)
+    data_alignment: Alignment,
+    /// Data offset from the header, see [`data_offset`](SarcFile::data_offset)
+    data_offset: Option<u32>,
+    /// File size from the header, see [`file_size`](SarcFile::file_size)
+    file_size: Option<u32>,
     pub files: Vec<SarcEntry>
 }
 
+impl SarcFile {
+    /// Create an archive with the given byte order from the files in `entries`
+    pub fn from_entries<I: IntoIterator<Item = SarcEntry>>(byte_order: Endian, entries: I) -> Self {
+        Self {
+            byte_order,
+            version: SARC_VERSION,
+            hash_key: HASH_KEY,
+            data_alignment: Alignment::DATA,
+            data_offset: None,
+            file_size: None,
+            files: entries.into_iter().collect()
+        }
+    }
+
+    /// Get the key the SFAT hashes of the archive were made with, as stored in its SFAT. This is
+    /// [`HASH_KEY`](HASH_KEY) for every known archive, as well as for archives not read from
+    /// bytes.
+    ///
+    /// **Note:** [`sfat_hash`](sfat_hash) always uses [`HASH_KEY`](HASH_KEY), so for an archive
+    /// with a different key the hashes of nameless files, and so lookups by hash such as
+    /// [`contains_hash`](SarcFile::contains_hash), won't match the hash of their name. Files with
+    /// names are unaffected, since they're found by name. Writing always uses
+    /// [`HASH_KEY`](HASH_KEY).
+    pub fn hash_key(&self) -> u32 {
+        self.hash_key
+    }
+
+    /// Get the alignment the start of the data section is written with when the
+    /// [`WriteOptions`](writer::WriteOptions) don't set one. This is
+    /// [`Alignment::DATA`](Alignment::DATA) unless changed with
+    /// [`with_data_alignment`](SarcFile::with_data_alignment) or set by
+    /// [`SarcBuilder::for_platform`](builder::SarcBuilder::for_platform). For an archive that was
+    /// read, it's inferred from where its data section starts.
+    pub fn data_alignment(&self) -> Alignment {
+        self.data_alignment
+    }
+
+    /// Consume the archive and return it with the given default data section alignment. See
+    /// [`data_alignment`](SarcFile::data_alignment).
+    pub fn with_data_alignment(mut self, alignment: Alignment) -> Self {
+        self.data_alignment = alignment;
+        self
+    }
+
+    /// Get the offset of the data section from the header of the archive this was read from, or
+    /// `None` if it wasn't read from bytes. This is the value as read and isn't updated when the
+    /// archive is modified; use [`serialized_size`](SarcFile::serialized_size) and
+    /// [`padding_overhead`](SarcFile::padding_overhead) for the layout it would be written with.
+    pub fn data_offset(&self) -> Option<u32> {
+        self.data_offset
+    }
+
+    /// Get the total size of the archive from the header of the archive this was read from, or
+    /// `None` if it wasn't read from bytes. Like [`data_offset`](SarcFile::data_offset) this is
+    /// the value as read; see [`serialized_size`](SarcFile::serialized_size) for the size it would
+    /// be written with.
+    pub fn file_size(&self) -> Option<u32> {
+        self.file_size
+    }
+
+    /// Change the byte order the archive is written in, such as when porting an archive between
+    /// the Wii U and Switch.
+    ///
+    /// **Note:** This only changes the byte order of the SARC header, SFAT and SFNT. The data of
+    /// each file is left exactly as it is, so files with endian-dependent formats of their own
+    /// (such as BFRES or BYML) have to be converted separately.
+    pub fn to_endian(&mut self, byte_order: Endian) {
+        self.byte_order = byte_order;
+    }
+
+    /// Consume the archive and return it with the given byte order. See
+    /// [`to_endian`](SarcFile::to_endian), which this shares its caveats with.
+    pub fn with_endian(mut self, byte_order: Endian) -> Self {
+        self.to_endian(byte_order);
+        self
+    }
+
+    /// Create an archive with the given byte order from a map of file names to data. Files are
+    /// sorted by name, so the archive doesn't depend on the map's iteration order.
+    #[cfg(feature = "std")]
+    pub fn from_map(byte_order: Endian, map: std::collections::HashMap<String, Vec<u8>>) -> Self {
+        let mut sarc = Self::from_entries(byte_order, map.into_iter().map(|(name, data)| {
+            SarcEntry::new(name, data)
+        }));
+        sarc.sort_files_by_name();
+        sarc
+    }
+
+    /// Convert the archive into a map of file names to data, dropping everything else. Nameless
+    /// files, including those whose name isn't valid UTF-8, are dropped too. If several files
+    /// share a name the first is kept, matching [`get`](SarcFile::get).
+    #[cfg(feature = "std")]
+    pub fn into_map(self) -> std::collections::HashMap<String, Vec<u8>> {
+        let mut map = std::collections::HashMap::with_capacity(self.files.len());
+        for file in self.files {
+            if let Some(name) = file.name {
+                map.entry(name).or_insert(file.data);
+            }
+        }
+
+        map
+    }
+
+    /// Iterate over the names of all the files in the archive, skipping nameless files
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().filter_map(|file| file.name.as_deref())
+    }
+
+    /// Get the number of files in the archive, including nameless files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check if the archive contains no files
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Remove every file from the archive
+    pub fn clear(&mut self) {
+        self.files.clear()
+    }
+
+    /// Shrink the capacity of `files`, and of the data of each file, as much as possible
+    pub fn shrink_to_fit(&mut self) {
+        self.files.shrink_to_fit();
+        for file in &mut self.files {
+            file.data.shrink_to_fit();
+        }
+    }
+
+    /// Get the total size of the data of every file in the archive, not including any padding.
+    /// See [`padding_overhead`](SarcFile::padding_overhead) for the padding.
+    pub fn total_data_size(&self) -> usize {
+        self.files.iter().map(|file| file.data.len()).sum()
+    }
+
+    /// Check if the archive contains a file with the given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Get the file with the given name, if there is one
+    pub fn get(&self, name: &str) -> Option<&SarcEntry> {
+        self.files.iter().find(|file| file.name.as_deref() == Some(name))
+    }
+
+    /// Get a mutable reference to the file with the given name, if there is one
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut SarcEntry> {
+        self.files.iter_mut().find(|file| file.name.as_deref() == Some(name))
+    }
+
+    /// Get every file whose name ends in the path component `basename`, such as both
+    /// `Actor/foo.bfres` and `Model/foo.bfres` for `foo.bfres`, in archive order. Nameless files
+    /// are skipped.
+    pub fn find_by_basename(&self, basename: &str) -> Vec<&SarcEntry> {
+        self.files.iter()
+            .filter(|file| {
+                file.name.as_deref()
+                    .is_some_and(|name| name.rsplit('/').next() == Some(basename))
+            })
+            .collect()
+    }
+
+    /// Rename the file named `old` to `new`, keeping its data. Returns `false` without changing
+    /// anything if there's no file named `old`, or if another file is already named `new`.
+    pub fn rename_file(&mut self, old: &str, new: impl Into<String>) -> bool {
+        let new = new.into();
+        if new != old && self.contains(&new) {
+            return false
+        }
+
+        match self.files.iter_mut().find(|file| file.name.as_deref() == Some(old)) {
+            Some(file) => {
+                file.set_name(Some(new));
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Sort the files in the archive using a comparator function. This only affects the order of
+    /// `files` in memory: when writing, the SFAT and data section are always in hash order, so the
+    /// order set here only decides the layout of files that share a hash, and the order of the
+    /// string table when writing with [`StringOrder::Files`](writer::StringOrder::Files).
+    pub fn sort_files_by<F>(&mut self, compare: F)
+        where F: FnMut(&SarcEntry, &SarcEntry) -> core::cmp::Ordering
+    {
+        self.files.sort_by(compare)
+    }
+
+    /// Sort the files in the archive alphabetically by name, with nameless files first. See
+    /// [`sort_files_by`](SarcFile::sort_files_by) for how this affects writing.
+    pub fn sort_files_by_name(&mut self) {
+        self.files.sort_by(|a, b| a.name_bytes().cmp(&b.name_bytes()))
+    }
+
+    /// Group the files in the archive by the top-level directory of their name, which is
+    /// everything before the first `/`. For example `Actor/Pack/foo` and `Actor/bar` are both
+    /// grouped under `Actor`. Files with no `/` in their name are grouped under `""`, and
+    /// nameless files are skipped. Files within each group are in archive order.
+    pub fn group_by_dir(&self) -> BTreeMap<&str, Vec<&SarcEntry>> {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for file in &self.files {
+            if let Some(name) = file.name.as_deref() {
+                let dir = name.find('/').map(|i| &name[..i]).unwrap_or("");
+                groups.entry(dir).or_default().push(file);
+            }
+        }
+
+        groups
+    }
+
+    /// Count the files in the archive by [`extension`](SarcEntry::extension). Files with no
+    /// extension, including nameless files, are counted under `""`.
+    pub fn entry_count_by_extension(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for file in &self.files {
+            *counts.entry(file.extension().unwrap_or("").to_owned()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Check if the archive contains a file with the given SFAT hash, including nameless files
+    pub fn contains_hash(&self, hash: u32) -> bool {
+        self.files.iter().any(|file| file.sfat_hash() == hash)
+    }
+
+    /// Check if two archives contain the same files, ignoring the order of the files, the byte
+    /// order and everything else that doesn't change what's in the archive. Files are matched by
+    /// name, or by SFAT hash for nameless files, and must have the same data.
+    ///
+    /// This is usually what's wanted when checking a round-trip. `SarcFile` doesn't implement
+    /// `PartialEq`, since a derived one would compare [`files`](SarcFile::files) positionally and
+    /// treat archives that only differ in file order as different.
+    pub fn semantically_eq(&self, other: &SarcFile) -> bool {
+        // (hash, name, data), sorted so the file order doesn't matter
+        type Contents<'a> = Vec<(u32, Option<&'a [u8]>, &'a [u8])>;
+
+        fn contents(sarc: &SarcFile) -> Contents<'_> {
+            let mut files: Vec<_> = sarc.files
+                .iter()
+                .map(|file| (file.sfat_hash(), file.name_bytes(), &file.data[..]))
+                .collect();
+            files.sort_unstable();
+            files
+        }
+
+        self.files.len() == other.files.len() && contents(self) == contents(other)
+    }
+}
+
+/// An empty little endian archive, the byte order used by the 3DS and Switch
+impl Default for SarcFile {
+    fn default() -> Self {
+        Self {
+            byte_order: Endian::Little,
+            version: SARC_VERSION,
+            hash_key: HASH_KEY,
+            data_alignment: Alignment::DATA,
+            data_offset: None,
+            file_size: None,
+            files: vec![]
+        }
+    }
+}
+
+/// Get the file with the given name.
+///
+/// # Panics
+///
+/// Panics if there is no file with the given name. Use [`get`](SarcFile::get) to handle that
+/// case instead.
+impl core::ops::Index<&str> for SarcFile {
+    type Output = SarcEntry;
+
+    fn index(&self, name: &str) -> &SarcEntry {
+        self.get(name).unwrap_or_else(|| panic!("no file named {:?} in archive", name))
+    }
+}
+
+/// Get a mutable reference to the file with the given name.
+///
+/// # Panics
+///
+/// Panics if there is no file with the given name. Use [`get_mut`](SarcFile::get_mut) to handle
+/// that case instead.
+impl core::ops::IndexMut<&str> for SarcFile {
+    fn index_mut(&mut self, name: &str) -> &mut SarcEntry {
+        self.get_mut(name).unwrap_or_else(|| panic!("no file named {:?} in archive", name))
+    }
+}
+
 /// A file contained within a Sarc archive
+#[derive(Clone, PartialEq, Eq)]
 pub struct SarcEntry {
     /// Filename of the file within the Sarc
     pub name: Option<String>,
+    /// Filename of the file as raw bytes, only used if the name isn't valid UTF-8. When reading,
+    /// this is only `Some` if `name` is `None`. When writing, `name` takes priority if both are set.
+    pub raw_name: Option<Vec<u8>>,
+    /// SFAT hash of a file without a name. When reading, this is only `Some` if the file has
+    /// neither a `name` nor a `raw_name`. When writing, files with a name always use the hash of
+    /// their name and files with no name or hash use a hash of 0.
+    pub hash: Option<u32>,
+    /// Alignment of the file's data within the data section. When reading, this is inferred from
+    /// where the file was placed: the largest power of two its offset is a multiple of, up to
+    /// [`Alignment::DATA`](Alignment::DATA). When writing, files with no alignment use
+    /// [`Alignment::DATA`](Alignment::DATA), so a read archive is written with the same layout.
+    pub alignment: Option<Alignment>,
     /// Data of the file
     pub data: Vec<u8>
 }
 
-impl std::fmt::Debug for SarcEntry {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self.name)
+impl SarcEntry {
+    /// Create a file with the given name and data
+    pub fn new<N: Into<String>, D: Into<Vec<u8>>>(name: N, data: D) -> Self {
+        Self { name: Some(name.into()), raw_name: None, hash: None, alignment: None, data: data.into() }
+    }
+
+    /// Get the filename of the file as bytes, regardless of whether it is valid UTF-8
+    pub fn name_bytes(&self) -> Option<&[u8]> {
+        self.as_entry_ref().name_bytes()
+    }
+
+    /// Get the SFAT hash of the file, computed from its name if it has one
+    pub fn sfat_hash(&self) -> u32 {
+        self.as_entry_ref().sfat_hash()
+    }
+
+    /// Borrow the file as a [`SarcEntryRef`](SarcEntryRef)
+    pub fn as_entry_ref(&self) -> SarcEntryRef<'_> {
+        SarcEntryRef {
+            name: self.name.as_deref(),
+            raw_name: self.raw_name.as_deref(),
+            hash: self.hash,
+            alignment: self.alignment,
+            data: &self.data
+        }
+    }
+
+    /// Get the extension of the file's name: everything after the last `.` in the part of the name
+    /// after the last `/`. Returns `None` if the file has no name or its name has no extension.
+    pub fn extension(&self) -> Option<&str> {
+        self.as_entry_ref().extension()
+    }
+
+    /// Guess the alignment the file's data needs from its magic. See
+    /// [`SarcEntryRef::guess_alignment`](SarcEntryRef::guess_alignment).
+    pub fn guess_alignment(&self) -> Option<Alignment> {
+        self.as_entry_ref().guess_alignment()
+    }
+
+    /// Set the filename of the file. This also clears `raw_name` and `hash`, since they describe
+    /// the old name.
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+        self.raw_name = None;
+        self.hash = None;
+    }
+
+    /// Replace the data of the file
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+    }
+
+    /// Get a mutable reference to the data of the file, for editing it in place
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+impl core::fmt::Debug for SarcEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match (&self.name, &self.raw_name) {
+            (None, Some(raw_name)) => write!(f, "Some({:?})", String::from_utf8_lossy(raw_name)),
+            _ => write!(f, "{:?}", self.name),
+        }
+    }
+}
+
+/// A file borrowing its name and data, for writing archives with
+/// [`write_entries`](writer::write_entries) without copying them into a
+/// [`SarcEntry`](SarcEntry). This is useful when packing many files whose names are string
+/// literals or whose data is already in memory elsewhere. The fields mean the same as those of
+/// [`SarcEntry`](SarcEntry).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SarcEntryRef<'a> {
+    pub name: Option<&'a str>,
+    pub raw_name: Option<&'a [u8]>,
+    pub hash: Option<u32>,
+    pub alignment: Option<Alignment>,
+    pub data: &'a [u8]
+}
+
+impl<'a> SarcEntryRef<'a> {
+    /// Create a file with the given name and data
+    pub fn new(name: &'a str, data: &'a [u8]) -> Self {
+        Self { name: Some(name), raw_name: None, hash: None, alignment: None, data }
+    }
+
+    /// Get the filename of the file as bytes, regardless of whether it is valid UTF-8
+    pub fn name_bytes(&self) -> Option<&'a [u8]> {
+        self.name.map(str::as_bytes).or(self.raw_name)
+    }
+
+    /// Get the SFAT hash of the file, computed from its name if it has one
+    pub fn sfat_hash(&self) -> u32 {
+        match (self.name, self.raw_name) {
+            (Some(name), _) => sfat_hash(name),
+            (None, Some(raw_name)) => sfat_hash_bytes(raw_name),
+            (None, None) => self.hash.unwrap_or_default(),
+        }
+    }
+
+    /// Get the extension of the file's name. See [`SarcEntry::extension`](SarcEntry::extension).
+    pub fn extension(&self) -> Option<&'a str> {
+        let name = self.name?;
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        file_name.rfind('.').map(|i| &file_name[i + 1..])
+    }
+
+    /// Guess the alignment the file's data needs from its magic, or `None` if it isn't a known
+    /// format. This is best-effort and only covers:
+    ///
+    /// | Format | Alignment |
+    /// |--------|-----------|
+    /// | Nested SARC | 0x2000 |
+    /// | Yaz0 | 0x80 |
+    /// | AAMP | 8 |
+    /// | Switch BFRES, BNTX, BNSH and FSHA | from the file's header, up to 0x2000 |
+    pub fn guess_alignment(&self) -> Option<Alignment> {
+        match self.data.get(..4)? {
+            b"SARC" => Some(Alignment::DATA),
+            b"Yaz0" => Alignment::new(0x80),
+            b"AAMP" => Alignment::new(8),
+            // Switch binary files store their alignment as a power of two after the byte order
+            // mark, which Wii U files don't have at that offset
+            b"FRES" | b"BNTX" | b"BNSH" | b"FSHA" => match self.data.get(0xC..0xF)? {
+                [0xFF, 0xFE, shift] | [0xFE, 0xFF, shift] => {
+                    1u32.checked_shl(*shift as u32)
+                        .and_then(Alignment::new)
+                        .map(|alignment| alignment.min(Alignment::DATA))
+                }
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// Copy the name and data into an owned [`SarcEntry`](SarcEntry)
+    pub fn to_owned_entry(&self) -> SarcEntry {
+        SarcEntry {
+            name: self.name.map(String::from),
+            raw_name: self.raw_name.map(Vec::from),
+            hash: self.hash,
+            alignment: self.alignment,
+            data: self.data.to_vec()
+        }
+    }
+}
+
+impl core::fmt::Debug for SarcEntryRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match (self.name, self.raw_name) {
+            (None, Some(raw_name)) => write!(f, "Some({:?})", String::from_utf8_lossy(raw_name)),
+            _ => write!(f, "{:?}", self.name),
+        }
     }
 }
 
 /// Byte order of the give sarc file
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Endian {
     Big = 0xFEFF,
     Little = 0xFFFE,
 }
 
-const KEY: u32 = 0x00000065;
+/// Compression applied to a whole sarc file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// An uncompressed sarc
+    None,
+    /// Yaz0 compression, usually with the file extension `.szs` or an `s`-prefixed extension
+    Yaz0,
+    /// Zstandard compression, usually with the file extension `.zs`
+    Zstd,
+    /// Not a recognized sarc file, compressed or otherwise
+    Unknown,
+}
+
+/// A console that uses SARC archives, for picking the right byte order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Platform {
+    WiiU,
+    ThreeDS,
+    Switch,
+}
+
+impl Endian {
+    /// Get the byte order of the platform this is running on
+    pub fn native() -> Endian {
+        if cfg!(target_endian = "big") {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Check if this is big endian
+    pub fn is_big(self) -> bool {
+        self == Endian::Big
+    }
+
+    /// Check if this is little endian
+    pub fn is_little(self) -> bool {
+        self == Endian::Little
+    }
+
+    /// Get the byte order SARC archives use on the given platform:
+    ///
+    /// | Platform | Byte order |
+    /// |----------|------------|
+    /// | Wii U    | Big        |
+    /// | 3DS      | Little     |
+    /// | Switch   | Little     |
+    pub fn for_platform(platform: Platform) -> Endian {
+        match platform {
+            Platform::WiiU => Endian::Big,
+            Platform::ThreeDS | Platform::Switch => Endian::Little,
+        }
+    }
+}
+
+/// A power-of-two alignment, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Alignment(u32);
+
+impl Alignment {
+    /// Alignment of the data section and of each file within it used by the writer
+    pub const DATA: Alignment = Alignment(0x2000);
+
+    /// Alignment of each name in the string table
+    pub const STRING: Alignment = Alignment(4);
+
+    /// Smallest alignment of the data section and of each file within it that the format allows
+    pub const MIN_DATA: Alignment = Alignment(4);
+
+    /// No alignment at all
+    pub const NONE: Alignment = Alignment(1);
+
+    /// Get the alignment of the start of the data section used by archives on the given platform:
+    ///
+    /// | Platform | Data offset alignment |
+    /// |----------|-----------------------|
+    /// | Wii U    | 0x100                 |
+    /// | 3DS      | 0x2000                |
+    /// | Switch   | 0x2000                |
+    pub fn data_offset_for_platform(platform: Platform) -> Alignment {
+        match platform {
+            Platform::WiiU => Alignment(0x100),
+            Platform::ThreeDS | Platform::Switch => Alignment::DATA,
+        }
+    }
+
+    /// Create an alignment of `n` bytes, or `None` if `n` isn't a power of two
+    pub fn new(n: u32) -> Option<Alignment> {
+        if n.is_power_of_two() {
+            Some(Alignment(n))
+        } else {
+            None
+        }
+    }
+
+    /// Get the alignment in bytes
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Round `offset` up to the next multiple of this alignment
+    pub fn align(self, offset: usize) -> usize {
+        let mask = self.0 as usize - 1;
+        (offset + mask) & !mask
+    }
+}
+
+/// The only SARC format version in use, written by all known games and tools
+pub const SARC_VERSION: u16 = 0x0100;
+
+/// The magic at the start of the SARC header
+pub const SARC_MAGIC: [u8; 4] = *b"SARC";
+
+/// The magic at the start of the SFAT header, which precedes the file table
+pub const SFAT_MAGIC: [u8; 4] = *b"SFAT";
+
+/// The magic at the start of the SFNT header, which precedes the string table
+pub const SFNT_MAGIC: [u8; 4] = *b"SFNT";
 
-/// Hashing function used for hashing sfat strings
+/// The key [`sfat_hash`](sfat_hash) multiplies by, stored in the SFAT of every known archive. See
+/// [`SarcFile::hash_key`](SarcFile::hash_key).
+pub const HASH_KEY: u32 = 0x00000065;
+
+/// Hashing function used for hashing sfat strings. The name is hashed as its UTF-8 bytes, see
+/// [`sfat_hash_bytes`](sfat_hash_bytes).
 pub fn sfat_hash(string: &str) -> u32 {
-    string.chars().fold(0u32, |hash, c| hash.wrapping_mul(KEY) + (c as u32))
+    sfat_hash_bytes(string.as_bytes())
+}
+
+/// Find the first candidate name whose [`sfat_hash`](sfat_hash) matches `hash`. Useful for
+/// recovering the names of nameless files from a wordlist.
+///
+/// **Note:** This is best-effort. The hash is only 32 bits and trivially collides, so a match
+/// isn't proof that the candidate is the original name.
+pub fn sfat_reverse<'a>(hash: u32, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates.iter()
+        .copied()
+        .find(|candidate| sfat_hash(candidate) == hash)
+}
+
+/// Hashing function used for hashing sfat strings that aren't valid UTF-8. Each byte is treated
+/// as a signed char, matching Nintendo's implementation.
+pub fn sfat_hash_bytes(string: &[u8]) -> u32 {
+    string.iter().fold(0u32, |hash, &c| hash.wrapping_mul(HASH_KEY).wrapping_add(c as i8 as u32))
 }
 
-#[cfg(test)]
+// the tests build their archives with the writer, which needs std
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use super::SarcFile;
+    use super::{SarcFile, SarcEntry, Endian, Platform, Alignment, SARC_VERSION, HASH_KEY, parser, writer};
+    use super::builder::SarcBuilder;
 
     #[test]
     fn file_test() {
@@ -74,4 +730,1670 @@ mod tests {
         file.write_to_file("test.sarc").unwrap();
         dbg!(file);
     }
+
+    fn test_archive() -> SarcFile {
+        SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("a.txt", vec![1, 2, 3]),
+            SarcEntry::new("dir/b.bin", vec![0; 0x2001]),
+            SarcEntry { name: None, raw_name: None, hash: Some(0x1234), alignment: None, data: vec![4, 5] },
+        ])
+    }
+
+    #[test]
+    fn serialized_size_test() {
+        let file = test_archive();
+        let mut out = vec![];
+        file.write(&mut out).unwrap();
+        assert_eq!(out.len(), file.serialized_size());
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn mmap_test() {
+        let file = test_archive();
+        let path = std::env::temp_dir().join("sarc_mmap_test.sarc");
+        file.write_to_file(&path).unwrap();
+        // SAFETY: the file was written by this test and nothing else touches it
+        let mapped = unsafe { crate::mmap::MappedSarc::open(&path) }.unwrap();
+        let read = SarcFile::read_from_file(&path).unwrap();
+        let entries = mapped.entries().unwrap();
+        assert_eq!(entries, read.files.iter().map(SarcEntry::as_entry_ref).collect::<Vec<_>>());
+        assert_eq!(mapped.get("dir/b.bin").unwrap(), Some(&[0; 0x2001][..]));
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn extract_one_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let full = SarcFile::read(&data).unwrap();
+
+        for entry in full.files.iter().filter(|entry| entry.name.is_some()) {
+            let name = entry.name.as_deref().unwrap();
+            assert_eq!(SarcFile::extract_one(&data, name).unwrap().as_ref(), Some(&entry.data));
+        }
+        assert_eq!(SarcFile::extract_one(&data, "missing.txt").unwrap(), None);
+    }
+
+    #[test]
+    fn version_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        assert_eq!(SarcFile::read(&data).unwrap().version, SARC_VERSION);
+
+        let data = SarcFile { version: 0x0200, ..test_archive() }.write_to_vec().unwrap();
+        assert!(matches!(SarcFile::read(&data), Err(parser::Error::UnsupportedVersion(0x0200))));
+    }
+
+    #[test]
+    fn header_validation_test() {
+        let data = test_archive().write_to_vec().unwrap();
+
+        let mut bad_size = data.clone();
+        bad_size[4] = 0x20;
+        assert!(matches!(SarcFile::read(&bad_size), Err(parser::Error::BadHeaderSize(0x20))));
+
+        let mut bad_offset = data.clone();
+        bad_offset[0xC..0x10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            SarcFile::read(&bad_offset),
+            Err(parser::Error::DataOffsetOutOfBounds(u32::MAX))
+        ));
+
+        let mut zero_offset = data.clone();
+        zero_offset[0xC..0x10].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            SarcFile::read(&zero_offset),
+            Err(parser::Error::DataOffsetOutOfBounds(0))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "yaz0_sarc")]
+    fn yaz0_round_trip_test() {
+        let mut archive = test_archive();
+        archive.files.truncate(1);
+        let data = archive.write_yaz0_to_vec().unwrap();
+        let file = SarcFile::read(&data).unwrap();
+        assert_eq!(file.files.len(), 1);
+        assert_eq!(SarcFile::extract_one(&data, "a.txt").unwrap(), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn try_read_no_panic_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let data = &data[..0x100];
+
+        // corrupt every byte of the header and SFAT in turn, and truncate at every length
+        for i in 0..data.len() {
+            for &byte in &[0x00, 0x7F, 0xFF] {
+                let mut corrupt = data.to_vec();
+                corrupt[i] = byte;
+                let _ = SarcFile::try_read(&corrupt);
+            }
+            let _ = SarcFile::try_read(&data[..i]);
+        }
+    }
+
+    #[test]
+    fn malformed_string_table_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let sfnt = data.windows(4).position(|w| w == b"SFNT").unwrap();
+
+        // name missing its NUL terminator, running into the data section
+        let mut unterminated = data.clone();
+        for byte in &mut unterminated[sfnt + 8..0x2000] {
+            *byte = b'a';
+        }
+        assert!(matches!(SarcFile::read(&unterminated), Err(parser::Error::MalformedStringTable)));
+
+        // SFNT header truncated by the data section starting early
+        let mut truncated = data;
+        truncated[0xC..0x10].copy_from_slice(&(sfnt as u32 + 4).to_le_bytes());
+        assert!(matches!(SarcFile::read(&truncated), Err(parser::Error::MalformedStringTable)));
+    }
+
+    #[test]
+    fn raw_name_test() {
+        let raw_name = b"bad\xFFname.bin".to_vec();
+        let file = SarcFile::from_entries(Endian::Big, vec![SarcEntry {
+            name: None, raw_name: Some(raw_name.clone()), hash: None, alignment: None, data: vec![1]
+        }]);
+        let data = file.write_to_vec().unwrap();
+
+        let file = SarcFile::read(&data).unwrap();
+        assert_eq!(file.files[0].name, None);
+        assert_eq!(file.files[0].raw_name.as_ref(), Some(&raw_name));
+        assert_eq!(file.files[0].name_bytes(), Some(&raw_name[..]));
+    }
+
+    #[test]
+    fn archive_too_large_test() {
+        assert_eq!(writer::checked_file_size(0x2000, 0x1000).unwrap(), 0x3000);
+        assert!(matches!(
+            writer::checked_file_size(0x2000, u32::MAX as usize),
+            Err(writer::Error::ArchiveTooLarge(0x1_0000_1FFF))
+        ));
+    }
+
+    #[test]
+    fn sfat_reverse_test() {
+        let candidates = ["b.txt", "a.txt", "c.txt"];
+        assert_eq!(super::sfat_reverse(super::sfat_hash("a.txt"), &candidates), Some("a.txt"));
+        assert_eq!(super::sfat_reverse(super::sfat_hash("d.txt"), &candidates), None);
+    }
+
+    #[test]
+    fn sfat_hash_non_ascii_test() {
+        let name = "Actor/Pack/\u{e9}\u{1F600}.bin";
+        assert_eq!(super::sfat_hash(name), super::sfat_hash_bytes(name.as_bytes()));
+        // bytes of multi-byte characters are signed, so each one subtracts from the hash
+        let expected = (0xC3u8 as i8 as u32).wrapping_mul(HASH_KEY).wrapping_add(0xA9u8 as i8 as u32);
+        assert_eq!(super::sfat_hash("\u{e9}"), expected);
+    }
+
+    #[test]
+    fn names_test() {
+        let file = test_archive();
+        assert_eq!(file.names().collect::<Vec<_>>(), ["a.txt", "dir/b.bin"]);
+        assert_eq!(file.len(), 3);
+        assert!(!file.is_empty());
+    }
+
+    #[test]
+    fn contains_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let file = SarcFile::read(&data).unwrap();
+        assert!(file.contains("a.txt"));
+        assert!(!file.contains("b.bin"));
+        assert!(file.contains_hash(super::sfat_hash("dir/b.bin")));
+        assert!(file.contains_hash(0x1234));
+        assert!(!file.contains_hash(0x4321));
+    }
+
+    #[test]
+    fn diff_test() {
+        let old = test_archive();
+        let mut new = test_archive();
+        new.files.remove(0);
+        new.files[0].data[0] = 1;
+        new.files.push(SarcEntry::new("c.txt", vec![]));
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, ["c.txt"]);
+        assert_eq!(diff.removed, ["a.txt"]);
+        assert_eq!(diff.changed, ["dir/b.bin"]);
+        assert_eq!(old.diff(&old), Default::default());
+    }
+
+    #[test]
+    fn presorted_write_test() {
+        let data = test_archive().write_to_vec().unwrap();
+
+        // a freshly read archive is already in hash order and must write back identically
+        let rewritten = SarcFile::read(&data).unwrap().write_to_vec().unwrap();
+        assert_eq!(data, rewritten);
+    }
+
+    #[test]
+    fn duplicate_name_test() {
+        let mut file = test_archive();
+        file.files.push(SarcEntry::new("a.txt", vec![4, 5, 6]));
+        assert!(matches!(
+            file.write(&mut vec![]),
+            Err(writer::Error::DuplicateName(name)) if name == "a.txt"
+        ));
+    }
+
+    #[test]
+    fn sort_files_test() {
+        let mut file = test_archive();
+        file.sort_files_by(|a, b| b.data.len().cmp(&a.data.len()));
+        assert_eq!(file.files[0].name.as_deref(), Some("dir/b.bin"));
+
+        file.sort_files_by_name();
+        let names: Vec<_> = file.files.iter().map(|file| file.name.as_deref()).collect();
+        assert_eq!(names, [None, Some("a.txt"), Some("dir/b.bin")]);
+    }
+
+    #[test]
+    fn header_endian_test() {
+        for &big_endian in &[false, true] {
+            let mut file = test_archive();
+            if big_endian {
+                file.byte_order = Endian::Big;
+            }
+            let mut data = file.write_to_vec().unwrap();
+
+            let (header_size, bom) = if big_endian {
+                ([0x00, 0x14], [0xFE, 0xFF])
+            } else {
+                ([0x14, 0x00], [0xFF, 0xFE])
+            };
+            assert_eq!(data[4..6], header_size);
+            assert_eq!(data[6..8], bom);
+
+            let read = SarcFile::read(&data).unwrap();
+            assert_eq!(read.version, SARC_VERSION);
+            assert!(matches!(
+                (read.byte_order, big_endian),
+                (Endian::Big, true) | (Endian::Little, false)
+            ));
+
+            // the header size must be read in the archive's byte order, not always little endian
+            data.swap(4, 5);
+            assert!(matches!(SarcFile::read(&data), Err(parser::Error::BadHeaderSize(0x1400))));
+        }
+    }
+
+    #[test]
+    fn endian_from_bom_test() {
+        assert_eq!(Endian::from_bom(0xFEFF).unwrap(), Endian::Big);
+        assert_eq!(Endian::from_bom(0xFFFE).unwrap(), Endian::Little);
+        assert!(matches!(Endian::from_bom(0x1234), Err(parser::Error::BadByteOrderMark(0x1234))));
+        assert_eq!(Endian::from(0xFEFF), Endian::Big);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd_sarc")]
+    fn zstd_round_trip_test() {
+        let data = test_archive().write_zstd_to_vec().unwrap();
+        let file = SarcFile::read(&data).unwrap();
+        assert_eq!(file.files, SarcFile::read(&test_archive().write_to_vec().unwrap()).unwrap().files);
+    }
+
+    #[test]
+    fn platform_test() {
+        assert!(matches!(Endian::for_platform(Platform::WiiU), Endian::Big));
+        assert!(matches!(Endian::for_platform(Platform::Switch), Endian::Little));
+
+        let file = SarcBuilder::for_platform(Platform::WiiU)
+            .file("a.txt", vec![1, 2, 3])
+            .build();
+        assert!(matches!(file.byte_order, Endian::Big));
+        assert_eq!(file.files[0].name.as_deref(), Some("a.txt"));
+        assert_eq!(file.data_alignment(), Alignment::new(0x100).unwrap());
+
+        // the preset's data alignment carries through to writing, unless the options override it
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(SarcFile::read(&data).unwrap().data_offset(), Some(0x100));
+        assert_eq!(data.len(), file.serialized_size());
+        let mut minimal = vec![];
+        file.write_minimal(&mut minimal).unwrap();
+        assert!(SarcFile::read(&minimal).unwrap().data_offset().unwrap() < 0x100);
+
+        let switch = SarcBuilder::for_platform(Platform::Switch).file("a.txt", vec![1]).build();
+        assert_eq!(SarcFile::read(&switch.write_to_vec().unwrap()).unwrap().data_offset(), Some(0x2000));
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn checksum_test() {
+        assert_eq!(super::checksum::crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut file = test_archive();
+        file.files.push(SarcEntry { hash: Some(0x5678), data: vec![6], ..file.files[2].clone() });
+        let checksums = file.checksums();
+        assert_eq!(checksums.len(), 4);
+        assert_eq!(checksums[0], super::checksum::crc32(&[1, 2, 3]));
+        assert_eq!(checksums[2], super::checksum::crc32(&[4, 5]));
+        assert_eq!(checksums[3], super::checksum::crc32(&[6]));
+    }
+
+    #[test]
+    fn as_sarc_test() {
+        let inner = test_archive().write_to_vec().unwrap();
+        let outer = SarcBuilder::new(Endian::Big)
+            .file("inner.sarc", inner)
+            .file("a.txt", vec![1, 2, 3])
+            .file("bad.sarc", b"SARC\0\0".to_vec())
+            .build();
+
+        let nested = outer.files[0].as_sarc().unwrap().unwrap();
+        assert_eq!(nested.len(), 3);
+        assert!(nested.contains("dir/b.bin"));
+        assert!(outer.files[1].as_sarc().is_none());
+        assert!(outer.files[2].as_sarc().unwrap().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd_sarc")]
+    fn decompressed_data_test() {
+        use super::Compression;
+        use std::borrow::Cow;
+
+        let mut file = test_archive();
+        assert!(!file.files[0].is_compressed());
+        assert!(matches!(file.files[0].decompressed_data().unwrap(), Cow::Borrowed([1, 2, 3])));
+
+        file.files[0].data = super::compress(&file.files[0].data, Compression::Zstd).unwrap();
+        assert!(file.files[0].is_compressed());
+        let data = file.files[0].decompressed_data().unwrap();
+        assert!(matches!(data, Cow::Owned(_)));
+        assert_eq!(*data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn flatten_test() {
+        let innermost = SarcBuilder::new(Endian::Little)
+            .file("c.txt", vec![3])
+            .build();
+        let inner = SarcBuilder::new(Endian::Little)
+            .file("b.txt", vec![2])
+            .file("pack.sarc", innermost.write_to_vec().unwrap())
+            .build();
+        let outer = SarcBuilder::new(Endian::Little)
+            .file("a.txt", vec![1])
+            .file("dir/inner.sarc", inner.write_to_vec().unwrap())
+            .build();
+
+        let mut names: Vec<_> = outer.flatten(8).names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "dir/inner.sarc/b.txt", "dir/inner.sarc/pack.sarc/c.txt"]);
+
+        let mut names: Vec<_> = outer.flatten(1).names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "dir/inner.sarc/b.txt", "dir/inner.sarc/pack.sarc"]);
+
+        assert_eq!(outer.flatten(0).files, outer.files);
+    }
+
+    #[test]
+    fn empty_archive_test() {
+        let file = SarcBuilder::new(Endian::Little).build();
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(data.len(), file.serialized_size());
+        assert_eq!(&data[0xC..0x10], &(data.len() as u32).to_le_bytes());
+
+        let file = SarcFile::read(&data).unwrap();
+        assert!(file.is_empty());
+    }
+
+    #[test]
+    fn merge_test() {
+        use super::merge::{Conflict, Error};
+
+        let base = || SarcBuilder::new(Endian::Little)
+            .file("a.txt", vec![1])
+            .file("b.txt", vec![2])
+            .build();
+        let patch = || SarcBuilder::new(Endian::Little)
+            .file("b.txt", vec![3])
+            .file("c.txt", vec![4])
+            .build();
+        let data = |file: &SarcFile| file.files.iter().map(|f| f.data[0]).collect::<Vec<_>>();
+
+        let mut merged = base();
+        merged.merge(patch(), Conflict::Skip).unwrap();
+        assert_eq!(data(&merged), [1, 2, 4]);
+
+        let mut merged = base();
+        merged.merge(patch(), Conflict::Overwrite).unwrap();
+        assert_eq!(data(&merged), [1, 3, 4]);
+
+        let mut merged = base();
+        assert!(matches!(
+            merged.merge(patch(), Conflict::Error),
+            Err(Error::Conflict(name)) if name == "b.txt"
+        ));
+        assert_eq!(data(&merged), [1, 2]);
+
+        let mut merged = base();
+        assert!(matches!(
+            merged.merge(SarcBuilder::new(Endian::Big).build(), Conflict::Skip),
+            Err(Error::ByteOrderMismatch(Endian::Little, Endian::Big))
+        ));
+    }
+
+    #[test]
+    fn group_by_dir_test() {
+        let file = SarcBuilder::new(Endian::Little)
+            .file("Actor/Pack/x", vec![])
+            .file("Physics/y", vec![])
+            .file("Actor/foo", vec![])
+            .file("root.txt", vec![])
+            .build();
+
+        let groups = file.group_by_dir();
+        let names = |dir| groups[dir].iter().map(|f| f.name.as_deref().unwrap()).collect::<Vec<_>>();
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), ["", "Actor", "Physics"]);
+        assert_eq!(names("Actor"), ["Actor/Pack/x", "Actor/foo"]);
+        assert_eq!(names(""), ["root.txt"]);
+    }
+
+    #[test]
+    fn native_endian_test() {
+        let native = Endian::native();
+        assert_eq!(native.is_big(), cfg!(target_endian = "big"));
+        assert_eq!(native.is_little(), !native.is_big());
+    }
+
+    #[test]
+    fn alignment_test() {
+        assert_eq!(Alignment::new(1).map(Alignment::get), Some(1));
+        assert_eq!(Alignment::new(0x2000).map(Alignment::get), Some(0x2000));
+        assert_eq!(Alignment::new(0), None);
+        assert_eq!(Alignment::new(3), None);
+        assert_eq!(Alignment::new(0x2001), None);
+
+        let alignment = Alignment::new(0x10).unwrap();
+        assert_eq!(alignment.align(0), 0);
+        assert_eq!(alignment.align(1), 0x10);
+        assert_eq!(alignment.align(0x10), 0x10);
+        assert_eq!(alignment.align(0x11), 0x20);
+    }
+
+    #[test]
+    fn read_with_metadata_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let (file, meta) = SarcFile::read_with_metadata(&data).unwrap();
+        assert_eq!(file.len(), meta.len());
+
+        for (file, meta) in file.files.iter().zip(&meta) {
+            assert_eq!(file.sfat_hash(), meta.hash);
+            assert_eq!(file.data.len(), meta.data_range.len());
+            assert_eq!(file.name.is_some(), meta.raw_attr & 0xFF00_0000 != 0);
+        }
+    }
+
+    #[test]
+    fn total_data_size_test() {
+        let file = test_archive();
+        assert_eq!(file.total_data_size(), 3 + 0x2001 + 2);
+
+        // header + SFAT + SFNT + "a.txt\0" + "dir/b.bin\0" padded to 4
+        let metadata = 0x14 + 0xC + 3 * 0x10 + 0x8 + 8 + 12;
+        assert_eq!(
+            file.padding_overhead(),
+            file.serialized_size() - file.total_data_size() - metadata
+        );
+    }
+
+    #[test]
+    fn read_at_test() {
+        let archive = test_archive().write_to_vec().unwrap();
+        let mut container = vec![0xAA; 0x30];
+        container.extend_from_slice(&archive);
+        container.extend_from_slice(&[0xBB; 0x10]);
+
+        let file = SarcFile::read_at(&container, 0x30).unwrap();
+        assert_eq!(file.files, SarcFile::read(&archive).unwrap().files);
+        assert!(SarcFile::read_at(&container, container.len() + 1).is_err());
+    }
+
+    #[test]
+    fn write_padded_test() {
+        let file = test_archive();
+        let unpadded = file.write_to_vec().unwrap();
+
+        let mut padded = vec![];
+        file.write_padded(&mut padded, Alignment::new(0x1000).unwrap()).unwrap();
+        assert_eq!(padded.len(), Alignment::new(0x1000).unwrap().align(unpadded.len()));
+        assert_eq!(&padded[0x8..0xC], &(padded.len() as u32).to_le_bytes());
+        assert_eq!(SarcFile::read(&padded).unwrap().files, SarcFile::read(&unpadded).unwrap().files);
+    }
+
+    #[test]
+    fn read_from_file_with_raw_test() {
+        let file = test_archive();
+        let path = std::env::temp_dir().join("sarc_with_raw_test.sarc");
+        file.write_to_file(&path).unwrap();
+
+        let (sarc, raw) = SarcFile::read_from_file_with_raw(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(raw, file.write_to_vec().unwrap());
+        assert_eq!(sarc.files, SarcFile::read(&raw).unwrap().files);
+    }
+
+    #[test]
+    fn entry_setters_test() {
+        let mut entry = SarcEntry { name: None, raw_name: Some(b"\xffold".to_vec()), hash: None, alignment: None, data: vec![] };
+        entry.set_name(Some("new.txt".into()));
+        assert_eq!(entry.name_bytes(), Some(&b"new.txt"[..]));
+        assert_eq!(entry.raw_name, None);
+
+        entry.set_data(vec![1, 2]);
+        entry.data_mut().push(3);
+        assert_eq!(entry.data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn string_order_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("z.txt", vec![1]),
+            SarcEntry::new("a.txt", vec![2]),
+        ]);
+        assert!(super::sfat_hash("z.txt") > super::sfat_hash("a.txt"));
+
+        let string_table = |data: &[u8]| data[0x14 + 0xC + 2 * 0x10 + 8..][..16].to_vec();
+
+        let hash_order = file.write_to_vec().unwrap();
+        assert_eq!(string_table(&hash_order), b"a.txt\0\0\0z.txt\0\0\0");
+
+        let mut files_order = vec![];
+        file.write_with_string_order(&mut files_order, writer::StringOrder::Files).unwrap();
+        assert_eq!(string_table(&files_order), b"z.txt\0\0\0a.txt\0\0\0");
+        assert_eq!(SarcFile::read(&files_order).unwrap().files, SarcFile::read(&hash_order).unwrap().files);
+    }
+
+    #[test]
+    fn try_from_test() {
+        use std::convert::TryFrom;
+
+        let data = test_archive().write_to_vec().unwrap();
+        let from_slice = SarcFile::try_from(&data[..]).unwrap();
+        let from_vec = SarcFile::try_from(data).unwrap();
+        assert_eq!(from_slice.files, from_vec.files);
+        assert!(SarcFile::try_from(&b"SARC"[..]).is_err());
+    }
+
+    #[test]
+    fn peek_compression_test() {
+        use super::Compression;
+
+        let data = test_archive().write_to_vec().unwrap();
+        assert_eq!(SarcFile::peek_compression(&data), Compression::None);
+        assert_eq!(SarcFile::peek_compression(b"Yaz0\0\0\0\0"), Compression::Yaz0);
+        assert_eq!(SarcFile::peek_compression(b"\x28\xB5\x2F\xFD"), Compression::Zstd);
+        assert_eq!(SarcFile::peek_compression(b"BNTX"), Compression::Unknown);
+        assert_eq!(SarcFile::peek_compression(b"SA"), Compression::Unknown);
+    }
+
+    #[test]
+    fn append_file_to_test() {
+        let mut file = test_archive();
+        let path = std::env::temp_dir().join("sarc_append_test.sarc");
+        file.write_to_file(&path).unwrap();
+
+        SarcFile::append_file_to(&path, "c/new.txt", &[7, 8, 9]).unwrap();
+        assert!(matches!(
+            SarcFile::append_file_to(&path, "a.txt", &[]),
+            Err(writer::Error::DuplicateName(_))
+        ));
+        let appended = SarcFile::read_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        file.files.push(SarcEntry::new("c/new.txt", vec![7, 8, 9]));
+        file.sort_files_by_name();
+        let mut appended_files = appended.files;
+        appended_files.sort_by(|a, b| a.name_bytes().cmp(&b.name_bytes()));
+        appended_files.iter_mut().for_each(|entry| entry.alignment = None);
+        assert_eq!(appended_files, file.files);
+    }
+
+    #[test]
+    fn append_file_to_layout_test() {
+        use writer::WriteOptions;
+
+        let path = std::env::temp_dir().join("sarc_append_layout_test.sarc");
+        let options = WriteOptions::new().data_alignment(Alignment::new(0x100).unwrap());
+        let mut data = vec![];
+        test_archive().write_with_options(&mut data, &options).unwrap();
+        // an unusual hash key, which the appended archive replaces along with the hashes
+        data[0x1C..0x20].copy_from_slice(&0x67u32.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        SarcFile::append_file_to(&path, "c.txt", &[7]).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let appended = SarcFile::read(&data).unwrap();
+        assert_eq!(appended.data_offset().unwrap() % 0x100, 0);
+        assert!(appended.data_offset().unwrap() < Alignment::DATA.get());
+        assert_eq!(appended.hash_key(), HASH_KEY);
+        for entry in SarcFile::list_entries(&data).unwrap() {
+            if let Some(name) = &entry.name {
+                assert_eq!(entry.hash, super::sfat_hash(name));
+            }
+        }
+        assert_eq!(appended["c.txt"].data, [7]);
+    }
+
+    #[test]
+    fn rename_file_test() {
+        let mut file = test_archive();
+        assert!(file.rename_file("a.txt", "c.txt"));
+        assert!(!file.contains("a.txt"));
+        assert_eq!(file.files[0].name.as_deref(), Some("c.txt"));
+        assert_eq!(file.files[0].data, [1, 2, 3]);
+
+        assert!(!file.rename_file("missing.txt", "d.txt"));
+        assert!(!file.contains("d.txt"));
+
+        assert!(!file.rename_file("c.txt", "dir/b.bin"));
+        assert!(file.contains("c.txt"));
+        assert_eq!(file.files[1].data.len(), 0x2001);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+    fn compress_stats_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![SarcEntry::new("a.txt", vec![0; 0x100])]);
+
+        let mut yaz0 = vec![];
+        let stats = file.write_yaz0_reporting(&mut yaz0).unwrap();
+        assert_eq!(stats.uncompressed, file.serialized_size());
+        assert_eq!(stats.compressed, yaz0.len());
+        assert!(stats.ratio() < 1.0);
+
+        let mut zstd = vec![];
+        let stats = file.write_zstd_reporting(&mut zstd).unwrap();
+        assert_eq!(stats.uncompressed, file.serialized_size());
+        assert_eq!(stats.compressed, zstd.len());
+    }
+
+    #[test]
+    fn write_nameless_test() {
+        let file = test_archive();
+        let mut data = vec![];
+        file.write_nameless(&mut data).unwrap();
+
+        let nameless = SarcFile::read(&data).unwrap();
+        assert_eq!(nameless.len(), file.len());
+        assert!(nameless.files.iter().all(|entry| entry.name_bytes().is_none()));
+        for entry in &file.files {
+            let found = nameless.files.iter().find(|e| e.hash == Some(entry.sfat_hash())).unwrap();
+            assert_eq!(found.data, entry.data);
+        }
+    }
+
+    #[test]
+    fn inferred_alignment_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("a.txt", vec![1, 2, 3]),
+            SarcEntry { alignment: Alignment::new(0x10), ..SarcEntry::new("b.txt", vec![4]) },
+        ]);
+        assert!(super::sfat_hash("a.txt") < super::sfat_hash("b.txt"));
+        let data = file.write_to_vec().unwrap();
+
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read.files[0].alignment, Some(Alignment::DATA));
+        assert_eq!(read.files[1].alignment, Alignment::new(0x10));
+        assert_eq!(read.write_to_vec().unwrap(), data);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd_sarc")]
+    fn read_with_compression_test() {
+        use super::Compression;
+
+        let file = test_archive();
+        let zstd = file.write_zstd_to_vec().unwrap();
+        let read = SarcFile::read_with_compression(&zstd, Compression::Zstd).unwrap();
+        assert_eq!(read.files, SarcFile::read(&zstd).unwrap().files);
+
+        let uncompressed = file.write_to_vec().unwrap();
+        assert!(SarcFile::read_with_compression(&uncompressed, Compression::None).is_ok());
+        assert!(SarcFile::read_with_compression(&uncompressed, Compression::Zstd).is_err());
+    }
+
+    #[test]
+    fn read_with_trailer_test() {
+        let mut data = test_archive().write_to_vec().unwrap();
+        let (_, trailer) = SarcFile::read_with_trailer(&data).unwrap();
+        assert!(trailer.is_empty());
+
+        data.extend_from_slice(b"SIGNATURE");
+        let (file, trailer) = SarcFile::read_with_trailer(&data).unwrap();
+        assert_eq!(trailer, b"SIGNATURE");
+        assert_eq!(file.len(), 3);
+    }
+
+    #[test]
+    fn large_round_trip_test() {
+        let file = super::testing::make_archive(200, 0x300);
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(data.len(), file.serialized_size());
+
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read.len(), 200);
+        assert_eq!(read.write_to_vec().unwrap(), data);
+    }
+
+    #[test]
+    fn data_alignment_test() {
+        let file = test_archive();
+        let alignment = Alignment::data_offset_for_platform(Platform::WiiU);
+        let mut data = vec![];
+        file.write_with_data_alignment(&mut data, alignment).unwrap();
+
+        let data_offset = u32::from_le_bytes([data[0xC], data[0xD], data[0xE], data[0xF]]);
+        assert_eq!(data_offset, 0x100);
+        assert_eq!(SarcFile::read(&data).unwrap().files, SarcFile::read(&file.write_to_vec().unwrap()).unwrap().files);
+    }
+
+    #[test]
+    fn index_test() {
+        let mut file = test_archive();
+        assert_eq!(file["a.txt"].data, [1, 2, 3]);
+        file["a.txt"].data.push(4);
+        assert_eq!(file.get("a.txt").unwrap().data, [1, 2, 3, 4]);
+        assert!(file.get("missing.txt").is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_missing_test() {
+        let _ = &test_archive()["missing.txt"];
+    }
+
+    #[test]
+    fn iter_by_offset_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let (file, meta) = SarcFile::read_with_metadata(&data).unwrap();
+
+        let starts: Vec<_> = file.iter_by_offset(&meta).map(|(_, meta)| meta.data_range.start).collect();
+        assert_eq!(starts.len(), 3);
+        assert!(starts.windows(2).all(|pair| pair[0] <= pair[1]));
+        for (entry, meta) in file.iter_by_offset(&meta) {
+            assert_eq!(entry.data.len(), meta.data_range.len());
+        }
+    }
+
+    #[test]
+    fn string_alignment_test() {
+        let file = test_archive();
+        let mut data = vec![];
+        file.write_with_string_alignment(&mut data, Alignment::new(8).unwrap()).unwrap();
+
+        let (read, meta) = SarcFile::read_with_metadata(&data).unwrap();
+        assert!(meta.iter().filter(|meta| meta.raw_attr != 0).all(|meta| (meta.raw_attr & 0xFFFF) % 2 == 0));
+        assert_eq!(read.files, SarcFile::read(&file.write_to_vec().unwrap()).unwrap().files);
+    }
+
+    #[test]
+    fn default_test() {
+        let file = SarcFile::default();
+        assert_eq!(file.byte_order, Endian::Little);
+        assert_eq!(file.version, SARC_VERSION);
+        assert!(file.is_empty());
+
+        let built = SarcBuilder::default().file("a.txt", vec![1]).build();
+        assert_eq!(built.byte_order, file.byte_order);
+        assert_eq!(built.len(), 1);
+    }
+
+    #[test]
+    fn extension_test() {
+        let mut file = test_archive();
+        assert_eq!(file.files[0].extension(), Some("txt"));
+        assert_eq!(file.files[2].extension(), None);
+
+        file.files.push(SarcEntry::new("dir.d/noext", vec![]));
+        file.files.push(SarcEntry::new("c.txt", vec![]));
+        assert_eq!(file.files[3].extension(), None);
+
+        let counts = file.entry_count_by_extension();
+        assert_eq!(counts.get("txt"), Some(&2));
+        assert_eq!(counts.get("bin"), Some(&1));
+        assert_eq!(counts.get(""), Some(&2));
+    }
+
+    #[test]
+    fn name_offset_into_data_test() {
+        let mut data = test_archive().write_to_vec().unwrap();
+        let data_offset = u32::from_le_bytes([data[0xC], data[0xD], data[0xE], data[0xF]]) as usize;
+        let string_table_start = 0x14 + 0xC + 3 * 0x10 + 8;
+
+        // point the first named file's name at the start of the data section
+        let attrs = (0..3).map(|i| 0x14 + 0xC + i * 0x10 + 4)
+            .find(|&attrs| data[attrs + 3] != 0)
+            .unwrap();
+        let name_offset = ((data_offset - string_table_start) / 4) as u16;
+        data[attrs..attrs + 2].copy_from_slice(&name_offset.to_le_bytes());
+
+        assert!(matches!(SarcFile::read(&data), Err(parser::Error::MalformedStringTable)));
+    }
+
+    #[test]
+    fn from_entries_test() {
+        let file = SarcFile::from_entries(Endian::Big, test_archive().files);
+        assert_eq!(file.byte_order, Endian::Big);
+        assert_eq!(file.version, SARC_VERSION);
+        assert_eq!(file.files, test_archive().files);
+    }
+
+    #[test]
+    fn multiple_nameless_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry { name: None, raw_name: None, hash: None, alignment: None, data: vec![1] },
+            SarcEntry { name: None, raw_name: None, hash: None, alignment: None, data: vec![2, 2] },
+            SarcEntry { name: None, raw_name: None, hash: None, alignment: None, data: vec![3, 3, 3] },
+        ]);
+        let read = SarcFile::read(&file.write_to_vec().unwrap()).unwrap();
+
+        let mut data: Vec<_> = read.files.iter().map(|entry| entry.data.clone()).collect();
+        data.sort();
+        assert_eq!(data, [vec![1], vec![2, 2], vec![3, 3, 3]]);
+    }
+
+    #[test]
+    fn hash_collision_test() {
+        assert_eq!(super::sfat_hash("itpdcnfu"), super::sfat_hash("ntrintqi"));
+
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("itpdcnfu", vec![1]),
+            SarcEntry::new("ntrintqi", vec![2, 2]),
+        ]);
+        let read = SarcFile::read(&file.write_to_vec().unwrap()).unwrap();
+
+        assert_eq!(read.len(), 2);
+        assert_eq!(read["itpdcnfu"].data, [1]);
+        assert_eq!(read["ntrintqi"].data, [2, 2]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+    fn read_with_limit_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("a.txt", vec![0; 0x100]),
+        ]);
+        let size = file.serialized_size();
+
+        let zstd = file.write_zstd_to_vec().unwrap();
+        assert!(SarcFile::read_with_limit(&zstd, size).is_ok());
+        assert!(matches!(
+            SarcFile::read_with_limit(&zstd, size - 1),
+            Err(parser::Error::DecompressionLimitExceeded)
+        ));
+
+        let yaz0 = file.write_yaz0_to_vec().unwrap();
+        assert!(SarcFile::read_with_limit(&yaz0, size).is_ok());
+        assert!(matches!(
+            SarcFile::read_with_limit(&yaz0, size - 1),
+            Err(parser::Error::DecompressionLimitExceeded)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "zstd_sarc")]
+    fn decompress_test() {
+        let file = test_archive();
+        let uncompressed = file.write_to_vec().unwrap();
+        assert_eq!(super::decompress(&file.write_zstd_to_vec().unwrap()).unwrap(), uncompressed);
+        assert_eq!(super::decompress(&uncompressed).unwrap(), uncompressed);
+    }
+
+    #[test]
+    fn compress_test() {
+        use super::Compression;
+
+        let data = b"some nested file some nested file".to_vec();
+        assert_eq!(super::compress(&data, Compression::None).unwrap(), data);
+        assert!(matches!(
+            super::compress(&data, Compression::Unknown),
+            Err(writer::Error::UnsupportedCompression(Compression::Unknown))
+        ));
+
+        #[cfg(feature = "zstd_sarc")] {
+            let zstd = super::compress(&data, Compression::Zstd).unwrap();
+            assert_eq!(SarcFile::peek_compression(&zstd), Compression::Zstd);
+        }
+        #[cfg(feature = "yaz0_sarc")] {
+            let yaz0 = super::compress(&data, Compression::Yaz0).unwrap();
+            assert_eq!(SarcFile::peek_compression(&yaz0), Compression::Yaz0);
+        }
+    }
+
+    #[test]
+    fn shrink_and_clear_test() {
+        let mut file = test_archive();
+        file.files.reserve(100);
+        file.files[0].data.reserve(100);
+        file.shrink_to_fit();
+        assert_eq!(file.files.capacity(), 3);
+        assert_eq!(file.files[0].data.capacity(), 3);
+
+        file.clear();
+        assert!(file.is_empty());
+    }
+
+    #[test]
+    fn read_uncompressed_test() {
+        let file = test_archive();
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(SarcFile::read_uncompressed(&data).unwrap().files, SarcFile::read(&data).unwrap().files);
+
+        #[cfg(feature = "zstd_sarc")]
+        assert!(SarcFile::read_uncompressed(&file.write_zstd_to_vec().unwrap()).is_err());
+    }
+
+    #[test]
+    fn stats_test() {
+        let file = SarcFile::read(&test_archive().write_to_vec().unwrap()).unwrap();
+        let stats = file.stats();
+        assert_eq!(stats.file_count, 3);
+        assert_eq!(stats.named_count, 2);
+        assert_eq!(stats.total_size, 3 + 0x2001 + 2);
+        assert_eq!(stats.largest_file.unwrap().name.as_deref(), Some("dir/b.bin"));
+        assert_eq!(stats.alignment_histogram.values().sum::<usize>(), 3);
+        assert!(stats.to_string().contains("files: 3 (2 named)"));
+
+        assert_eq!(SarcFile::default().stats().largest_file, None);
+    }
+
+    #[test]
+    fn zero_length_file_test() {
+        let empty = || SarcEntry::new("empty.txt", vec![]);
+
+        let alone = SarcFile::from_entries(Endian::Little, vec![empty()]);
+        let read = SarcFile::read(&alone.write_to_vec().unwrap()).unwrap();
+        assert_eq!(read.len(), 1);
+        assert!(read["empty.txt"].data.is_empty());
+
+        let mut interleaved = test_archive();
+        interleaved.files.insert(1, empty());
+        let read = SarcFile::read(&interleaved.write_to_vec().unwrap()).unwrap();
+        assert_eq!(read.len(), 4);
+        assert!(read["empty.txt"].data.is_empty());
+        assert_eq!(read["a.txt"].data, [1, 2, 3]);
+        assert_eq!(read["dir/b.bin"].data, vec![0; 0x2001]);
+    }
+
+    #[test]
+    fn write_minimal_test() {
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("a.txt", vec![1, 2, 3]),
+            SarcEntry::new("b.txt", vec![4, 5]),
+        ]);
+        let mut data = vec![];
+        file.write_minimal(&mut data).unwrap();
+        assert!(data.len() < 0x100);
+        assert!(data.len() < file.write_to_vec().unwrap().len());
+
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read["a.txt"].data, [1, 2, 3]);
+        assert_eq!(read["b.txt"].data, [4, 5]);
+    }
+
+    #[test]
+    fn trailing_padding_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let mut padded = data.clone();
+        padded.resize(data.len() + 0x1000, 0);
+
+        let (file, meta) = SarcFile::read_with_metadata(&data).unwrap();
+        let (padded_file, padded_meta) = SarcFile::read_with_metadata(&padded).unwrap();
+        assert_eq!(padded_file.files, file.files);
+        assert_eq!(padded_meta, meta);
+    }
+
+    #[test]
+    fn binwrite_endian_test() {
+        assert!(matches!(binwrite::Endian::from(Endian::Big), binwrite::Endian::Big));
+        assert!(matches!(binwrite::Endian::from(Endian::Little), binwrite::Endian::Little));
+        assert_eq!(Endian::from(binwrite::Endian::Big), Endian::Big);
+        assert_eq!(Endian::from(binwrite::Endian::Native), Endian::native());
+    }
+
+    #[test]
+    fn normalize_separators_test() {
+        use super::dir::normalize_separators;
+
+        assert_eq!(normalize_separators("Actor\\Pack\\foo.bactorpack"), "Actor/Pack/foo.bactorpack");
+        assert_eq!(normalize_separators("Actor/Pack\\foo.bactorpack"), "Actor/Pack/foo.bactorpack");
+        assert_eq!(normalize_separators("foo.txt"), "foo.txt");
+    }
+
+    #[test]
+    fn from_dir_test() {
+        let dir = std::env::temp_dir().join("sarc_from_dir_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("Actor/Pack")).unwrap();
+        std::fs::write(dir.join("Actor/Pack/Foo.txt"), [1]).unwrap();
+        std::fs::write(dir.join("bar.bin"), [2, 3]).unwrap();
+
+        let file = SarcFile::from_dir(&dir, Endian::Big).unwrap();
+        let lowercase = SarcFile::from_dir_with(&dir, Endian::Big, |name| name.to_lowercase()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.names().collect::<Vec<_>>(), ["Actor/Pack/Foo.txt", "bar.bin"]);
+        assert_eq!(file["bar.bin"].data, [2, 3]);
+        assert_eq!(lowercase.names().collect::<Vec<_>>(), ["actor/pack/foo.txt", "bar.bin"]);
+    }
+
+    #[test]
+    fn hash_collision_order_test() {
+        let forward = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("itpdcnfu", vec![1]), SarcEntry::new("ntrintqi", vec![2, 2]),
+        ]);
+        let reverse = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("ntrintqi", vec![2, 2]), SarcEntry::new("itpdcnfu", vec![1]),
+        ]);
+
+        let written = forward.write_to_vec().unwrap();
+        assert_eq!(written, reverse.write_to_vec().unwrap());
+        assert_eq!(SarcFile::read(&written).unwrap().names().collect::<Vec<_>>(), ["itpdcnfu", "ntrintqi"]);
+    }
+
+    #[test]
+    fn write_with_options_test() {
+        use writer::WriteOptions;
+
+        let file = test_archive();
+        let mut plain = vec![];
+        file.write(&mut plain).unwrap();
+        let mut defaults = vec![];
+        file.write_with_options(&mut defaults, &WriteOptions::default()).unwrap();
+        assert_eq!(plain, defaults);
+
+        let mut minimal = vec![];
+        file.write_minimal(&mut minimal).unwrap();
+        let mut combined = vec![];
+        let options = WriteOptions::new()
+            .data_alignment(Alignment::MIN_DATA)
+            .minimal_padding(true)
+            .file_alignment(Alignment::new(0x100).unwrap());
+        file.write_with_options(&mut combined, &options).unwrap();
+        assert_eq!(combined.len() % 0x100, 0);
+        // Only the file size in the header and the end padding differ from `write_minimal`
+        assert_eq!(combined[..0x8], minimal[..0x8]);
+        assert_eq!(combined[0xC..minimal.len()], minimal[0xC..]);
+        assert!(combined[minimal.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn read_with_options_test() {
+        use super::Compression;
+        use parser::ReadOptions;
+
+        let data = test_archive().write_to_vec().unwrap();
+        assert_eq!(
+            SarcFile::read_with_options(&data, &ReadOptions::default()).unwrap().files,
+            SarcFile::read(&data).unwrap().files
+        );
+
+        let file = SarcFile::read_with_options(&data, &ReadOptions::new().preserve_layout(false)).unwrap();
+        assert!(file.files.iter().all(|file| file.alignment.is_none()));
+        assert!(SarcFile::read(&data).unwrap().files.iter().all(|file| file.alignment.is_some()));
+
+        // an uncompressed archive can't be read as compressed
+        assert!(SarcFile::read_with_options(&data, &ReadOptions::new().compression(Compression::Yaz0)).is_err());
+    }
+
+    #[test]
+    fn read_with_options_raw_names_test() {
+        use parser::ReadOptions;
+
+        let raw_name = b"bad\xFFname.bin".to_vec();
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry { name: None, raw_name: Some(raw_name.clone()), hash: None, alignment: None, data: vec![1] },
+        ]);
+        let data = file.write_to_vec().unwrap();
+
+        let file = SarcFile::read_with_options(&data, &ReadOptions::new().keep_raw_names(false)).unwrap();
+        assert_eq!(file.files[0].name, None);
+        assert_eq!(file.files[0].raw_name, None);
+        assert_eq!(file.files[0].hash, Some(super::sfat_hash_bytes(&raw_name)));
+        assert_eq!(file.files[0].data, [1]);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd_sarc")]
+    fn read_with_options_compression_test() {
+        use super::Compression;
+        use parser::ReadOptions;
+
+        let file = test_archive();
+        let size = file.serialized_size();
+        let zstd = file.write_zstd_to_vec().unwrap();
+
+        let options = ReadOptions::new().compression(Compression::Zstd);
+        assert_eq!(
+            SarcFile::read_with_options(&zstd, &options).unwrap().files,
+            SarcFile::read(&zstd).unwrap().files
+        );
+        assert!(SarcFile::read_with_options(&zstd, &ReadOptions::new().compression(Compression::None)).is_err());
+
+        assert!(SarcFile::read_with_options(&zstd, &options.max_decompressed(size)).is_ok());
+        assert!(matches!(
+            SarcFile::read_with_options(&zstd, &options.max_decompressed(size - 1)),
+            Err(parser::Error::DecompressionLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn to_endian_test() {
+        let little = test_archive().with_endian(Endian::Little).write_to_vec().unwrap();
+        assert_eq!(little[6..8], [0xFF, 0xFE]);
+
+        let mut file = SarcFile::read(&little).unwrap();
+        file.to_endian(Endian::Big);
+        let big = file.write_to_vec().unwrap();
+        assert_eq!(big[6..8], [0xFE, 0xFF]);
+
+        let read = SarcFile::read(&big).unwrap();
+        assert_eq!(read.byte_order, Endian::Big);
+        assert_eq!(read.files, SarcFile::read(&little).unwrap().files);
+    }
+
+    #[test]
+    fn data_order_test() {
+        use writer::{DataOrder, WriteOptions};
+
+        // all three files share a hash, so only the data order decides between them
+        let hash = super::sfat_hash("itpdcnfu");
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("ntrintqi", vec![1; 1]),
+            SarcEntry { name: None, raw_name: None, hash: Some(hash), alignment: None, data: vec![3; 3] },
+            SarcEntry::new("itpdcnfu", vec![2; 2]),
+        ]);
+        let data_order = |order| {
+            let mut data = vec![];
+            file.write_with_options(&mut data, &WriteOptions::new().data_order(order)).unwrap();
+            let (read, meta) = SarcFile::read_with_metadata(&data).unwrap();
+            read.iter_by_offset(&meta).map(|(file, _)| file.data[0]).collect::<Vec<_>>()
+        };
+
+        assert_eq!(data_order(DataOrder::Files), [1, 3, 2]);
+        assert_eq!(data_order(DataOrder::Size), [1, 2, 3]);
+        assert_eq!(data_order(DataOrder::Name), [3, 2, 1]);
+    }
+
+    #[test]
+    fn hash_key_test() {
+        let mut data = test_archive().write_to_vec().unwrap();
+        assert_eq!(SarcFile::read(&data).unwrap().hash_key(), HASH_KEY);
+
+        // the hash key follows the SFAT magic, header size and node count
+        assert_eq!(data[0x1C..0x20], HASH_KEY.to_le_bytes());
+        data[0x1C..0x20].copy_from_slice(&0x67u32.to_le_bytes());
+        let file = SarcFile::read(&data).unwrap();
+        assert_eq!(file.hash_key(), 0x67);
+
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(data[0x1C..0x20], HASH_KEY.to_le_bytes());
+    }
+
+    #[test]
+    fn write_to_file_atomic_test() {
+        let dir = std::env::temp_dir().join("sarc_atomic_test");
+        let path = dir.join("a.sarc");
+        std::fs::create_dir_all(&dir).unwrap();
+        let only_path = || {
+            let entries: Vec<_> = std::fs::read_dir(&dir).unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            entries == [path.clone()]
+        };
+
+        // an existing file that happens to look like a temporary file isn't overwritten
+        let stale = dir.join("a.sarc.tmp");
+        std::fs::write(&stale, b"stale").unwrap();
+        let file = test_archive();
+        file.write_to_file_atomic(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), file.write_to_vec().unwrap());
+        assert_eq!(std::fs::read(&stale).unwrap(), b"stale");
+        std::fs::remove_file(&stale).unwrap();
+        assert!(only_path());
+
+        // a duplicate name fails the write after the temporary file was created
+        let mut duplicate = test_archive();
+        duplicate.files.push(duplicate.files[0].clone());
+        assert!(matches!(
+            duplicate.write_to_file_atomic(&path),
+            Err(writer::Error::DuplicateName(_))
+        ));
+        assert!(only_path());
+        assert_eq!(std::fs::read(&path).unwrap(), file.write_to_vec().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_by_basename_test() {
+        let file = SarcBuilder::default()
+            .file("Actor/foo.bfres", vec![1])
+            .file("Model/foo.bfres", vec![2])
+            .file("foo.bfres", vec![3])
+            .file("Actor/barfoo.bfres", vec![4])
+            .build();
+
+        let found: Vec<_> = file.find_by_basename("foo.bfres").iter().map(|file| file.data[0]).collect();
+        assert_eq!(found, [1, 2, 3]);
+        assert!(file.find_by_basename("Actor").is_empty());
+        assert_eq!(test_archive().find_by_basename("b.bin").len(), 1);
+    }
+
+    #[test]
+    fn write_entries_test() {
+        use super::SarcEntryRef;
+
+        let data = [1, 2, 3];
+        let entries = [
+            SarcEntryRef::new("a.txt", &data),
+            SarcEntryRef::new("dir/b.bin", &[0; 0x2001]),
+            SarcEntryRef { name: None, raw_name: None, hash: Some(0x1234), alignment: None, data: &[4, 5] },
+        ];
+        let mut written = vec![];
+        writer::write_entries(&mut written, Endian::Little, &entries, &writer::WriteOptions::new()).unwrap();
+
+        assert_eq!(written, test_archive().write_to_vec().unwrap());
+        assert_eq!(
+            entries.iter().map(SarcEntryRef::to_owned_entry).collect::<Vec<_>>(),
+            test_archive().files
+        );
+        assert_eq!(test_archive().files[0].as_entry_ref(), entries[0]);
+    }
+
+    #[test]
+    fn header_layout_test() {
+        let file = test_archive();
+        assert_eq!(file.data_offset(), None);
+        assert_eq!(file.file_size(), None);
+
+        let mut data = vec![];
+        file.write_padded(&mut data, Alignment::new(0x100).unwrap()).unwrap();
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read.data_offset(), Some(0x2000));
+        assert_eq!(read.file_size(), Some(data.len() as u32));
+        assert_eq!(read.flatten(1).data_offset(), None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+    fn read_double_compressed_test() {
+        use super::Compression;
+        use parser::ReadOptions;
+
+        // yaz0 compression is slow, so keep the archive small
+        let mut data = vec![];
+        SarcBuilder::default().file("a.txt", vec![1, 2, 3]).build().write_minimal(&mut data).unwrap();
+        let single = super::compress(&data, Compression::Yaz0).unwrap();
+        let double = super::compress(&super::compress(&data, Compression::Zstd).unwrap(), Compression::Yaz0)
+            .unwrap();
+
+        assert!(matches!(
+            SarcFile::read_with_options(&double, &ReadOptions::new()),
+            Err(parser::Error::CompressionDepthExceeded(1))
+        ));
+        let read = SarcFile::read_with_options(&double, &ReadOptions::new().max_compression_depth(2)).unwrap();
+        assert_eq!(read.files, SarcFile::read(&data).unwrap().files);
+
+        // extra depth is only used if needed
+        assert!(SarcFile::read_with_options(&single, &ReadOptions::new().max_compression_depth(3)).is_ok());
+        assert!(matches!(
+            SarcFile::read_with_options(&single, &ReadOptions::new().max_compression_depth(0)),
+            Err(parser::Error::CompressionDepthExceeded(0))
+        ));
+    }
+
+    #[test]
+    fn list_entries_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let entries = SarcFile::list_entries(&data).unwrap();
+        let (file, meta) = SarcFile::read_with_metadata(&data).unwrap();
+
+        assert_eq!(entries.len(), file.len());
+        for ((entry, file), meta) in entries.iter().zip(&file.files).zip(&meta) {
+            assert_eq!(entry.name, file.name);
+            assert_eq!(entry.hash, file.sfat_hash());
+            assert_eq!(entry.range, meta.data_range);
+            assert_eq!(entry.range.len(), file.data.len());
+        }
+    }
+
+    #[test]
+    fn duplicate_names_test() {
+        use writer::WriteOptions;
+
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            SarcEntry::new("a.txt", vec![1]), SarcEntry::new("a.txt", vec![2, 2]),
+        ]);
+        assert!(matches!(file.write_to_vec(), Err(writer::Error::DuplicateName(_))));
+
+        let options = WriteOptions::new().allow_duplicate_names(true);
+        let mut data = vec![];
+        file.write_with_options(&mut data, &options).unwrap();
+
+        // the SFAT nodes follow the 0x14 byte header and 0xC byte SFAT header
+        assert_eq!(data[0x20 + 7], 1);
+        assert_eq!(data[0x30 + 7], 2);
+
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(read.files.iter().all(|file| file.name.as_deref() == Some("a.txt")));
+        assert_eq!(read.files[0].data, [1]);
+        assert_eq!(read.files[1].data, [2, 2]);
+
+        let mut rewritten = vec![];
+        read.write_with_options(&mut rewritten, &options).unwrap();
+        assert_eq!(rewritten, data);
+    }
+
+    #[test]
+    fn collision_count_test() {
+        let file = SarcBuilder::default()
+            .file("itpdcnfu", vec![1])
+            .file("ntrintqi", vec![2])
+            .build();
+        let data = file.write_to_vec().unwrap();
+        assert_eq!(data[0x20 + 7], 1);
+        assert_eq!(data[0x30 + 7], 2);
+    }
+
+    #[test]
+    fn into_map_test() {
+        let map = test_archive().into_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a.txt"], [1, 2, 3]);
+        assert_eq!(map["dir/b.bin"], vec![0; 0x2001]);
+
+        let file = SarcFile::from_map(Endian::Big, map.clone());
+        assert_eq!(file.byte_order, Endian::Big);
+        assert_eq!(file.names().collect::<Vec<_>>(), ["a.txt", "dir/b.bin"]);
+        assert_eq!(file.into_map(), map);
+    }
+
+    #[test]
+    fn alignment_table_test() {
+        use writer::WriteOptions;
+
+        let entry = |name: &str, alignment, data: &[u8]| SarcEntry { alignment, ..SarcEntry::new(name, data) };
+        let file = SarcFile::from_entries(Endian::Little, vec![
+            entry("a.bfres", None, &[1]),
+            entry("b.bfres", Alignment::new(0x40), &[2]),
+            entry("c.szs", None, b"Yaz0\0\0\0\0"),
+            entry("d.bin", None, &[3]),
+        ]);
+        let table = vec![("bfres".to_string(), Alignment::new(0x100).unwrap())].into_iter().collect();
+        let options = WriteOptions::new().minimal_padding(true).alignment_table(table);
+
+        let offsets = |options: &WriteOptions| {
+            let mut data = vec![];
+            file.write_with_options(&mut data, options).unwrap();
+            let (read, meta) = SarcFile::read_with_metadata(&data).unwrap();
+            read.files.iter()
+                .zip(meta)
+                .map(|(file, meta)| (file.name.clone().unwrap(), meta.data_range.start))
+                .collect::<std::collections::HashMap<_, _>>()
+        };
+
+        let unguessed = offsets(&options);
+        assert_eq!(unguessed["a.bfres"] % 0x100, 0);
+        assert_eq!(unguessed["b.bfres"] % 0x40, 0);
+
+        let guessed = offsets(&options.clone().guess_alignment(true));
+        assert_eq!(guessed["c.szs"] % 0x80, 0);
+
+        assert_eq!(entry("c.szs", None, b"Yaz0").guess_alignment(), Alignment::new(0x80));
+        assert_eq!(entry("d.bin", None, &[3]).guess_alignment(), None);
+        let mut bntx = b"BNTX\0\0\0\0\0\0\0\0\xFF\xFE\x0C".to_vec();
+        assert_eq!(entry("e.bntx", None, &bntx).guess_alignment(), Alignment::new(0x1000));
+        bntx[0xE] = 0x10;
+        assert_eq!(entry("e.bntx", None, &bntx).guess_alignment(), Alignment::new(0x2000));
+    }
+
+    #[test]
+    fn semantically_eq_test() {
+        let file = test_archive();
+        let mut other = test_archive().with_endian(Endian::Big);
+        other.files.reverse();
+        assert!(file.semantically_eq(&other));
+
+        let mut out = vec![];
+        other.write(&mut out).unwrap();
+        let written = SarcFile::read(&out).unwrap();
+        assert!(file.semantically_eq(&written));
+
+        other.files[0].data_mut().push(0);
+        assert!(!file.semantically_eq(&other));
+
+        other.files.pop();
+        assert!(!file.semantically_eq(&other));
+        assert!(!other.semantically_eq(&file));
+    }
+
+    #[test]
+    fn custom_magics_test() {
+        let file = test_archive();
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+
+        let sfnt = 0x14 + 0xC + 0x10 * file.files.len();
+        assert_eq!(&data[sfnt..sfnt + 4], &super::SFNT_MAGIC);
+        data[..4].copy_from_slice(b"SRC2");
+        data[0x14..0x18].copy_from_slice(b"SFA2");
+        data[sfnt..sfnt + 4].copy_from_slice(b"SFN2");
+
+        assert!(SarcFile::read(&data).is_err());
+        let options = parser::ReadOptions::new().magics(*b"SRC2", *b"SFA2", *b"SFN2");
+        let read = SarcFile::read_with_options(&data, &options).unwrap();
+        assert!(file.semantically_eq(&read));
+
+        let options = parser::ReadOptions::new().magics(*b"SRC2", *b"SFA2", super::SFNT_MAGIC);
+        assert!(SarcFile::read_with_options(&data, &options).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+    fn compress_progress_test() {
+        let file = SarcBuilder::default().file("a.txt", vec![0; 0x100]).build();
+        let total = file.serialized_size();
+        let check = |calls: &[(usize, usize)]| {
+            assert!(calls.iter().all(|&(_, t)| t == total));
+            assert!(calls.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert_eq!(calls.last(), Some(&(total, total)));
+        };
+
+        let mut calls = vec![];
+        let mut yaz0 = vec![];
+        file.write_yaz0_with_progress(&mut yaz0, |processed, total| calls.push((processed, total)))
+            .unwrap();
+        check(&calls);
+        assert_eq!(yaz0, file.write_yaz0_to_vec().unwrap());
+
+        let mut calls = vec![];
+        let mut zstd = vec![];
+        file.write_zstd_with_progress(&mut zstd, |processed, total| calls.push((processed, total)))
+            .unwrap();
+        check(&calls);
+        assert_eq!(zstd, file.write_zstd_to_vec().unwrap());
+    }
+
+    #[test]
+    fn read_lossy_test() {
+        let file = test_archive();
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+
+        let (read, warnings) = SarcFile::read_lossy(&data).unwrap();
+        assert!(warnings.is_empty());
+        assert!(file.semantically_eq(&read));
+
+        // point the end of the second node's data past the end of the archive
+        let node = 0x20 + 0x10;
+        data[node + 0xC..node + 0x10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SarcFile::read(&data).is_err());
+
+        let (read, warnings) = SarcFile::read_lossy(&data).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].index, 1);
+        assert!(matches!(warnings[0].reason, parser::Error::ParseError(_)));
+        assert_eq!(read.len(), 2);
+
+        assert!(SarcFile::read_lossy(&data[..0x10]).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip_test() {
+        let file = test_archive();
+        let data = super::write_bytes(&file).unwrap();
+        assert_eq!(data, file.write_to_vec().unwrap());
+        assert_eq!(super::read_bytes(&data).unwrap().files, SarcFile::read(&data).unwrap().files);
+    }
+
+    #[test]
+    fn compress_entry_test() {
+        use super::Compression;
+
+        let mut file = test_archive();
+        assert!(!file.compress_entry("missing.txt", Compression::None).unwrap());
+        assert!(file.compress_entry("a.txt", Compression::None).unwrap());
+        assert_eq!(file["a.txt"].data, [1, 2, 3]);
+
+        #[cfg(feature = "zstd_sarc")] {
+            assert!(file.compress_entry("a.txt", Compression::Zstd).unwrap());
+            assert_eq!(SarcFile::peek_compression(&file["a.txt"].data), Compression::Zstd);
+            assert_eq!(super::decompress(&file["a.txt"].data).unwrap(), [1, 2, 3]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "yaz0_sarc")]
+    fn compress_entries_in_place_test() {
+        use super::Compression;
+
+        let mut file = SarcBuilder::default()
+            .file("Model/a.sbfres", vec![1; 0x10])
+            .file("Model/a.bfres", vec![2; 0x10])
+            .file("Pack/b.sarc", vec![3; 0x10])
+            .file("Pack/c.s", vec![4; 0x10])
+            .file("d.sbyml", super::writer::compress(&[5; 0x10], Compression::Yaz0).unwrap())
+            .file("Pack/e.szs", vec![6; 0x10])
+            .file("Sound/f.sabin", vec![7; 0x10])
+            .build();
+
+        assert_eq!(file.compress_entries_in_place().unwrap(), 2);
+        assert_eq!(SarcFile::peek_compression(&file["Model/a.sbfres"].data), Compression::Yaz0);
+        assert_eq!(super::decompress(&file["Model/a.sbfres"].data).unwrap(), [1; 0x10]);
+        assert_eq!(file["Model/a.bfres"].data, [2; 0x10]);
+        assert_eq!(file["Pack/b.sarc"].data, [3; 0x10]);
+        assert_eq!(file["Pack/c.s"].data, [4; 0x10]);
+        assert_eq!(super::decompress(&file["d.sbyml"].data).unwrap(), [5; 0x10]);
+        assert_eq!(super::decompress(&file["Pack/e.szs"].data).unwrap(), [6; 0x10]);
+        assert_eq!(file["Sound/f.sabin"].data, [7; 0x10]);
+
+        assert_eq!(file.compress_entries_in_place().unwrap(), 0);
+    }
+
+    #[test]
+    fn layout_report_test() {
+        let mut file = test_archive();
+        file.files[0].alignment = Alignment::new(0x100);
+        let report = file.layout_report();
+
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+        let (read, meta) = SarcFile::read_with_metadata(&data).unwrap();
+        assert_eq!(report.len(), read.len());
+        for (file, meta) in read.iter_by_offset(&meta) {
+            assert!(report.iter().any(|(name, start, end, _)| {
+                *name == file.name
+                    && *start as usize == meta.data_range.start
+                    && *end as usize == meta.data_range.end
+            }));
+        }
+
+        let mut previous_end = 0;
+        for (_, start, end, padding_before) in &report {
+            assert_eq!(*start as usize, previous_end + padding_before);
+            previous_end = *end as usize;
+        }
+        let padding: usize = report.iter().map(|(_, _, _, padding)| padding).sum();
+        assert_eq!(read.data_offset().unwrap() as usize + previous_end, data.len());
+        assert_eq!(previous_end, file.total_data_size() + padding);
+    }
+
+    #[test]
+    fn large_string_table_test() {
+        // over 256 KiB of names, so name offsets no longer fit in 16 bits once divided by 4
+        let files: Vec<_> = (0..6000)
+            .map(|i| SarcEntry::new(format!("{:060}", i), vec![i as u8]))
+            .collect();
+        let file = SarcFile::from_entries(Endian::Little, files);
+
+        let data = file.write_to_vec().unwrap();
+        assert!(data.len() > 0x40000);
+        assert!(SarcFile::read(&data).unwrap().semantically_eq(&file));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_to_file_atomic_permissions_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("sarc_atomic_permissions_test");
+        let path = dir.join("a.sarc");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, b"old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        test_archive().write_to_file_atomic(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_data_alignment_test() {
+        let alignment = Alignment::new(0x100).unwrap();
+        let file = test_archive().with_data_alignment(alignment);
+        let data = file.write_to_vec().unwrap();
+
+        let read = SarcFile::read(&data).unwrap();
+        assert_eq!(read.data_alignment(), alignment);
+        assert_eq!(read.write_to_vec().unwrap(), data);
+    }
+
+    #[test]
+    fn read_entries_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let read = SarcFile::read(&data).unwrap();
+        let entries = SarcFile::read_entries(&data).unwrap();
+        assert_eq!(entries, read.files.iter().map(SarcEntry::as_entry_ref).collect::<Vec<_>>());
+        assert!(entries.iter().all(|entry| data.as_ptr_range().contains(&entry.data.as_ptr())));
+    }
+
+    #[test]
+    #[cfg(feature = "wasm")]
+    fn wasm_test() {
+        let data = test_archive().write_to_vec().unwrap();
+        let mut names = crate::wasm::list(&data).unwrap();
+        names.sort();
+        assert_eq!(names, ["a.txt", "dir/b.bin"]);
+        assert_eq!(crate::wasm::extract(&data, "a.txt").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(crate::wasm::extract(&data, "missing").unwrap(), None);
+
+        let repacked = crate::wasm::repack(&data, "a.txt", vec![4]).unwrap();
+        let repacked = crate::wasm::repack(&repacked, "c.txt", vec![5]).unwrap();
+        let read = SarcFile::read(&repacked).unwrap();
+        assert_eq!(read.get("a.txt").unwrap().data, [4]);
+        assert_eq!(read.get("c.txt").unwrap().data, [5]);
+        assert_eq!(read.files.len(), 4);
+        assert!(matches!(
+            crate::wasm::list(&data[..0x10]),
+            Err(crate::wasm::Error::ReadError(_))
+        ));
+    }
+
+    #[test]
+    fn too_many_files_test() {
+        use writer::lowlevel::{Sfat, SfatEntry};
+
+        let files = (0..0x10000)
+            .map(|hash| SarcEntry { name: None, raw_name: None, hash: Some(hash), alignment: None, data: vec![] })
+            .collect::<Vec<_>>();
+        let file = SarcFile::from_entries(Endian::Little, files);
+        assert!(matches!(file.write_to_vec(), Err(writer::Error::TooManyFiles(0x10000))));
+
+        let sfat = Sfat { entries: vec![SfatEntry::new(0, None, (0, 0)); 0x10000] };
+        let err = binwrite::BinWrite::write(&sfat, &mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn data_past_file_size_test() {
+        let mut data = test_archive().write_to_vec().unwrap();
+        let file_size = data.len();
+        let data_offset = u32::from_le_bytes([data[0xC], data[0xD], data[0xE], data[0xF]]) as usize;
+        data.extend_from_slice(&[0; 0x10]);
+        assert!(SarcFile::read(&data).is_ok());
+
+        // end the first node's data in the bytes trailing the archive
+        let end = 0x14 + 0xC + 0xC;
+        let past_file_size = (file_size - data_offset + 8) as u32;
+        data[end..end + 4].copy_from_slice(&past_file_size.to_le_bytes());
+        assert!(matches!(SarcFile::read(&data), Err(parser::Error::ParseError(_))));
+    }
 }
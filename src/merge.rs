@@ -0,0 +1,61 @@
+use super::{SarcFile, Endian};
+use alloc::string::String;
+
+/// What to do when merging a file whose name is already in the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// Keep the existing file and ignore the new one
+    Skip,
+    /// Replace the existing file's data with the new one
+    Overwrite,
+    /// Fail the merge with [`Error::Conflict`](Error::Conflict)
+    Error,
+}
+
+/// An error raised while merging two archives
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The archives have different byte orders
+    ByteOrderMismatch(Endian, Endian),
+
+    /// Both archives contain a file with the given name and the policy was [`Conflict::Error`](Conflict::Error)
+    Conflict(String),
+}
+
+impl SarcFile {
+    /// Merge the files of `other` into this archive, such as when layering a mod's archive over
+    /// the base game's. Files with the same name are resolved according to `on_conflict`, and
+    /// nameless files are always added.
+    ///
+    /// If this returns an error, this archive is left unchanged.
+    pub fn merge(&mut self, other: SarcFile, on_conflict: Conflict) -> Result<(), Error> {
+        if self.byte_order != other.byte_order {
+            return Err(Error::ByteOrderMismatch(self.byte_order, other.byte_order));
+        }
+
+        if on_conflict == Conflict::Error {
+            if let Some(name) = other.files.iter()
+                .filter_map(|file| file.name_bytes())
+                .find(|&name| self.position_of(name).is_some())
+            {
+                return Err(Error::Conflict(String::from_utf8_lossy(name).into_owned()));
+            }
+        }
+
+        for file in other.files {
+            match file.name_bytes().and_then(|name| self.position_of(name)) {
+                Some(i) => if on_conflict == Conflict::Overwrite {
+                    self.files[i] = file;
+                }
+                None => self.files.push(file)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn position_of(&self, name: &[u8]) -> Option<usize> {
+        self.files.iter().position(|file| file.name_bytes() == Some(name))
+    }
+}
@@ -0,0 +1,66 @@
+//! Memory-mapped reading of sarc files with [`memmap2`](https://docs.rs/memmap2). Requires the
+//! `mmap` feature.
+use super::{SarcFile, SarcEntryRef, Compression};
+use super::parser::Error;
+use std::path::Path;
+
+/// A sarc file mapped into memory, whose files can be read in place without copying them. Only the
+/// pages of the files actually read are loaded from disk, which makes this much cheaper than
+/// [`read_from_file`](SarcFile::read_from_file) for opening a large archive to extract a few
+/// files.
+///
+/// Compressed archives can't be read in place, so they're decompressed into memory when opened
+/// instead, and the mapping is dropped.
+pub struct MappedSarc {
+    data: Backing,
+}
+
+enum Backing {
+    Mapped(memmap2::Mmap),
+    Decompressed(Vec<u8>),
+}
+
+impl MappedSarc {
+    /// Map the sarc file (with or without compression) at `path` into memory.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    ///
+    /// # Safety
+    ///
+    /// The contents of the mapping are backed by the file on disk. If the file is truncated or
+    /// modified while it is mapped, the data borrowed from the `MappedSarc` changes or faults,
+    /// which is undefined behavior. The caller must ensure nothing (including other processes)
+    /// truncates or writes to the file until the `MappedSarc` is dropped. See
+    /// [`memmap2::Mmap::map`](https://docs.rs/memmap2/0.9/memmap2/struct.Mmap.html#method.map).
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = std::fs::File::open(path.as_ref()).map_err(Error::IoError)?;
+        let map = memmap2::Mmap::map(&file).map_err(Error::IoError)?;
+
+        let data = match SarcFile::peek_compression(&map) {
+            Compression::Yaz0 | Compression::Zstd => Backing::Decompressed(crate::decompress(&map)?),
+            _ => Backing::Mapped(map),
+        };
+
+        Ok(Self { data })
+    }
+
+    /// Get the raw bytes of the uncompressed sarc file
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.data {
+            Backing::Mapped(map) => map,
+            Backing::Decompressed(data) => data,
+        }
+    }
+
+    /// Read the files of the archive, borrowing their names and data from the mapping. See
+    /// [`SarcFile::read_entries`](SarcFile::read_entries).
+    pub fn entries(&self) -> Result<Vec<SarcEntryRef<'_>>, Error> {
+        SarcFile::read_entries(self.as_bytes())
+    }
+
+    /// Get the data of the file with the given name, borrowed from the mapping. Returns `Ok(None)`
+    /// if no file with that name exists.
+    pub fn get(&self, name: &str) -> Result<Option<&[u8]>, Error> {
+        Ok(self.entries()?.into_iter().find(|entry| entry.name == Some(name)).map(|entry| entry.data))
+    }
+}
@@ -1,52 +1,81 @@
 use nom::{
     IResult,
-    bytes::complete::tag,
+    bytes::complete::{tag, take},
     sequence::tuple,
     multi::count,
     number::complete::*
 };
-use super::{SarcFile, SarcEntry, Endian};
-use std::ops::Range;
+use super::{SarcFile, SarcEntry, SarcEntryRef, Endian, Compression, Alignment, sfat_hash, sfat_reverse};
+use super::{SARC_VERSION, SARC_MAGIC, SFAT_MAGIC, SFNT_MAGIC};
+use alloc::{borrow::Cow, format, string::{String, ToString}, vec, vec::Vec};
+use core::convert::TryFrom;
+use core::ops::Range;
 
+/// Get the byte order from a byte order mark read as big endian
+///
+/// **Note:** Deprecated, as this panics if `val` isn't a valid byte order mark. Use
+/// [`Endian::from_bom`](Endian::from_bom) instead.
 impl From<u16> for Endian {
     fn from(val: u16) -> Self {
+        Self::from_bom(val).expect("invalid byte order mark")
+    }
+}
+
+impl Endian {
+    /// Get the byte order from a byte order mark read as big endian, erroring with
+    /// [`Error::BadByteOrderMark`](Error::BadByteOrderMark) if it's neither 0xFEFF nor 0xFFFE
+    pub fn from_bom(val: u16) -> Result<Self, Error> {
         match val {
-            0xFEFF => Self::Big,
-            0xFFFE => Self::Little,
-            _ => panic!()
+            0xFEFF => Ok(Self::Big),
+            0xFFFE => Ok(Self::Little),
+            _ => Err(Error::BadByteOrderMark(val))
         }
     }
 }
 
 #[allow(dead_code)]
 struct SarcHeader {
+    header_size: u16,
     byte_order: Endian,
     file_size: u32,
     data_offset: u32,
+    version: u16,
 }
 
 struct SfatNode {
-    name_offset: Option<u16>,
+    hash: u32,
+    attrs: u32,
+    name_offset: Option<u32>,
     file_range: Range<usize>,
 }
 
 
-fn parse_sfat<E: TakeEndian>(data: &[u8]) -> IResult<&[u8], (u32, Vec<SfatNode>)> {
+/// Parse the SFAT header and nodes from `data`, which must end where the data section starts
+fn parse_sfat<'a, E: TakeEndian>(data: &'a [u8], magic: &[u8; 4])
+    -> Result<(&'a [u8], u32, Vec<SfatNode>), Error>
+{
     let (data, (
         _,
         _,
         node_count,
         hash_key
     )) = tuple((
-        tag(b"SFAT"),
+        tag(&magic[..]),
         take_u16::<E>,
         take_u16::<E>,
         take_u32::<E>
-    ))(data)?;
+    ))(data).map_err(nom_error)?;
 
-    let (data, files) = count::<_, _, NE, _>(|data| {
+    // check up front so a bad count gets a clearer error than running out of data partway through
+    let available = data.len() / SFAT_NODE_SIZE;
+    if node_count as usize > available {
+        return Err(Error::TruncatedSfat { declared: node_count, available });
+    }
+    debug!(node_count, hash_key, "parsed SFAT header");
+
+    let (data, files) = count(|data| {
         let (data, (
-            _,
+            hash,
             file_attrs,
             file_start,
             file_end
@@ -55,171 +84,1078 @@ fn parse_sfat<E: TakeEndian>(data: &[u8]) -> IResult<&[u8], (u32, Vec<SfatNode>)
             take_u32::<E>,
             take_u32::<E>,
             take_u32::<E>,
-        ))(data).unwrap();
+        ))(data)?;
 
-        const HAS_NAME: u32 = 0x01000000;
+        // the top byte is 1 for named files, or a collision count starting from 1 for files whose
+        // names share a hash
+        const COLLISION_COUNT: u32 = 0xFF000000;
+        // the rest is a 24-bit offset into the string table, in units of 4 bytes
+        const NAME_OFFSET: u32 = 0x00FFFFFF;
 
-        let name_offset = if file_attrs & HAS_NAME != 0 {
-            Some(file_attrs as u16)
+        let name_offset = if file_attrs & COLLISION_COUNT != 0 {
+            Some(file_attrs & NAME_OFFSET)
         } else {
             None
         };
+        trace!(hash, attrs = file_attrs, data = ?(file_start..file_end), "parsed SFAT node");
 
         Ok((data, SfatNode{
+            hash,
+            attrs: file_attrs,
             name_offset,
             file_range: (file_start as usize..file_end as usize)
         }))
-    }, node_count as _)(data).unwrap();
-    
-    Ok((data, (hash_key, files)))
+    }, node_count as _)(data).map_err(nom_error)?;
+
+    Ok((data, hash_key, files))
 }
 
-fn get_string(slice: &[u8], offset: usize) -> Option<String> {
-    for i in offset..slice.len() {
-        if slice[i] == 0 {
-            return std::str::from_utf8(&slice[offset..i]).ok().map(String::from)
-        }
-    }
-    None
+/// Parse the SFNT header, returning everything following it
+fn parse_sfnt<'a, E: TakeEndian>(data: &'a [u8], magic: &[u8; 4]) -> IResult<&'a [u8], ()> {
+    let (data, (
+        _,
+        header_size,
+        _
+    )) = tuple((
+        tag(&magic[..]),
+        take_u16::<E>,
+        take_u16::<E>
+    ))(data)?;
+
+    let (data, _) = take((header_size as usize).saturating_sub(SFNT_HEADER_SIZE))(data)?;
+
+    Ok((data, ()))
+}
+
+/// Get the NUL-terminated string at `offset`, or `None` if it runs past the end of `slice`
+fn get_string(slice: &[u8], offset: usize) -> Option<&[u8]> {
+    let string = slice.get(offset..)?;
+    let len = string.iter().position(|&c| c == 0)?;
+    Some(&string[..len])
 }
 
-type NE<'a> = (&'a [u8], nom::error::ErrorKind);
+const SARC_HEADER_SIZE: u16 = 0x14;
+const YAZ0_MAGIC: &[u8; 4] = b"Yaz0";
+const ZSTD_MAGIC: &[u8; 4] = b"\x28\xB5\x2F\xFD";
+const SFNT_HEADER_SIZE: usize = 8;
+const SFAT_NODE_SIZE: usize = 0x10;
 
 /// An error while reading the file
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "std")]
     IoError(std::io::Error),
 
     ParseError(String),
 
     #[cfg(feature = "yaz0_sarc")]
+    #[deprecated(note = "no longer returned, invalid yaz0 data is reported as a `ParseError`")]
     Yaz0Error(yaz0::Error),
+
+    /// The archive declares a SARC version this crate doesn't know how to read
+    UnsupportedVersion(u16),
+
+    /// The header-size field of the SARC header isn't 0x14
+    BadHeaderSize(u16),
+
+    /// The byte order mark of the SARC header is neither 0xFEFF nor 0xFFFE
+    BadByteOrderMark(u16),
+
+    /// The data offset of the SARC header points past the end of the file
+    DataOffsetOutOfBounds(u32),
+
+    /// The SFAT declares more nodes than fit between its header and the data section
+    TruncatedSfat {
+        /// The node count stored in the SFAT header
+        declared: u16,
+        /// How many whole nodes fit before the data section
+        available: usize,
+    },
+
+    /// The SFNT header is missing or truncated, or a name points outside of the string table
+    MalformedStringTable,
+
+    /// The archive would decompress to more than the limit given to
+    /// [`read_with_limit`](SarcFile::read_with_limit)
+    DecompressionLimitExceeded,
+
+    /// The archive was still compressed after decompressing it the given number of times, the
+    /// limit set by [`ReadOptions::max_compression_depth`](ReadOptions::max_compression_depth)
+    CompressionDepthExceeded(usize),
+}
+
+
+/// Settings for reading an archive with [`read_with_options`](SarcFile::read_with_options).
+/// Every option starts at the value [`read`](SarcFile::read) uses and can be changed with the
+/// method of the same name.
+///
+/// ```rust
+/// use sarc::{SarcFile, parser::ReadOptions};
+///
+/// let options = ReadOptions::new()
+///     .max_decompressed(0x100_0000)
+///     .keep_raw_names(false);
+///
+/// # #[cfg(feature = "std")] {
+/// let mut data = vec![];
+/// SarcFile::default().write(&mut data).unwrap();
+/// let sarc = SarcFile::read_with_options(&data, &options).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    max_decompressed: usize,
+    preserve_layout: bool,
+    keep_raw_names: bool,
+    compression: Option<Compression>,
+    max_compression_depth: usize,
+    magics: Magics,
 }
 
-use std::io::Cursor;
+/// The section magics to expect, see [`ReadOptions::magics`](ReadOptions::magics)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Magics {
+    sarc: [u8; 4],
+    sfat: [u8; 4],
+    sfnt: [u8; 4],
+}
+
+impl Default for Magics {
+    fn default() -> Self {
+        Self { sarc: SARC_MAGIC, sfat: SFAT_MAGIC, sfnt: SFNT_MAGIC }
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            max_decompressed: usize::MAX,
+            preserve_layout: true,
+            keep_raw_names: true,
+            compression: None,
+            max_compression_depth: 1,
+            magics: Magics::default(),
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Create options matching [`read`](SarcFile::read)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return [`Error::DecompressionLimitExceeded`](Error::DecompressionLimitExceeded) instead of
+    /// decompressing more than `limit` bytes. See [`read_with_limit`](SarcFile::read_with_limit).
+    /// Defaults to no limit.
+    pub fn max_decompressed(mut self, limit: usize) -> Self {
+        self.max_decompressed = limit;
+        self
+    }
+
+    /// Set the [`alignment`](SarcEntry::alignment) of each file from where its data was placed,
+    /// so writing the archive again keeps the same layout. When `false` every file's alignment is
+    /// left as `None` and writing uses the default. Defaults to `true`.
+    pub fn preserve_layout(mut self, preserve_layout: bool) -> Self {
+        self.preserve_layout = preserve_layout;
+        self
+    }
+
+    /// Keep names that aren't valid UTF-8 in [`raw_name`](SarcEntry::raw_name). When `false`
+    /// such files are read as nameless, keeping only their hash. Defaults to `true`.
+    pub fn keep_raw_names(mut self, keep_raw_names: bool) -> Self {
+        self.keep_raw_names = keep_raw_names;
+        self
+    }
+
+    /// Decompress with the given compression instead of detecting it from the magic. See
+    /// [`read_with_compression`](SarcFile::read_with_compression). Defaults to detecting it.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Decompress up to `depth` nested layers of compression, such as a yaz0 file wrapping a zstd
+    /// file wrapping the archive, which some tools produce by mistake. If the data is still
+    /// compressed after that many layers reading fails with
+    /// [`Error::CompressionDepthExceeded`](Error::CompressionDepthExceeded). Only the first layer
+    /// uses [`compression`](ReadOptions::compression), the rest are detected from their magic, and
+    /// [`max_decompressed`](ReadOptions::max_decompressed) applies to each layer. Defaults to 1.
+    pub fn max_compression_depth(mut self, depth: usize) -> Self {
+        self.max_compression_depth = depth;
+        self
+    }
+
+    /// Expect the given magics at the start of the SARC, SFAT and SFNT headers instead of
+    /// [`SARC_MAGIC`](crate::SARC_MAGIC), [`SFAT_MAGIC`](crate::SFAT_MAGIC) and
+    /// [`SFNT_MAGIC`](crate::SFNT_MAGIC). The standard magics are no longer accepted.
+    ///
+    /// **Note:** This is only meant for reverse-engineering nonstandard archives that are
+    /// otherwise laid out like SARC. Detecting compression still relies on the standard magics,
+    /// so set [`compression`](ReadOptions::compression) if such an archive is compressed.
+    pub fn magics(mut self, sarc: [u8; 4], sfat: [u8; 4], sfnt: [u8; 4]) -> Self {
+        self.magics = Magics { sarc, sfat, sfnt };
+        self
+    }
+}
+
+/// Decompress `data` if it is yaz0 or zstd compressed, otherwise borrow it as-is.
+fn decompress_if_needed(data: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    decompress_if_needed_with_limit(data, usize::MAX)
+}
+
+/// Decompress `data` if it is yaz0 or zstd compressed, erroring if it would decompress to more than
+/// `limit` bytes, otherwise borrow it as-is.
+fn decompress_if_needed_with_limit(data: &[u8], limit: usize) -> Result<Cow<'_, [u8]>, Error> {
+    if data.len() < 4 {
+        return Err(Error::ParseError("Input buffer must be at least 4 bytes".into()));
+    }
+    decompress_with_limit(data, SarcFile::peek_compression(data), limit)
+}
+
+/// Read a sarc file (with or without compression) from a byte slice. The same as
+/// [`SarcFile::read`](SarcFile::read), as a free function for bindings such as wasm that only
+/// pass bytes in and out. Together with [`write_bytes`](crate::write_bytes),
+/// [`SarcFile::list_entries`](SarcFile::list_entries) and
+/// [`SarcFile::extract_one`](SarcFile::extract_one) this covers working with archives without
+/// any filesystem access.
+///
+/// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+pub fn read_bytes(data: &[u8]) -> Result<SarcFile, Error> {
+    SarcFile::read(data)
+}
+
+/// Decompress a yaz0 or zstd compressed sarc file without parsing it, returning the raw SARC
+/// bytes. Data that isn't compressed is returned unchanged.
+///
+/// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    decompress_if_needed(data).map(Cow::into_owned)
+}
+
+/// Decompress `data` using the given compression, borrowing it as-is if it's uncompressed.
+fn decompress_with(data: &[u8], compression: Compression) -> Result<Cow<'_, [u8]>, Error> {
+    decompress_with_limit(data, compression, usize::MAX)
+}
+
+/// Decompress `data` using the given compression, erroring if it would decompress to more than
+/// `limit` bytes, and borrowing it as-is if it's uncompressed.
+#[cfg_attr(not(any(feature = "yaz0_sarc", feature = "zstd_sarc")), allow(unused_variables))]
+fn decompress_with_limit(data: &[u8], compression: Compression, limit: usize)
+    -> Result<Cow<'_, [u8]>, Error>
+{
+    match compression {
+        Compression::Yaz0 => {
+            #[cfg(feature = "yaz0_sarc")] {
+                // yaz0 declares its decompressed size up front, and never decompresses past it
+                let declared_size = data.get(4..8)
+                    .map(|size| u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize);
+                if declared_size.is_some_and(|size| size > limit) {
+                    return Err(Error::DecompressionLimitExceeded);
+                }
+                yaz0_decompress(data).map(Cow::Owned)
+            }
+            #[cfg(not(feature = "yaz0_sarc"))] {
+                Err(Error::ParseError(
+                    "Yaz0 compression detected but yaz0_sarc feature not enabled.".into()
+                ))
+            }
+        }
+        Compression::Zstd => {
+            #[cfg(feature = "zstd_sarc")] {
+                use std::io::Read;
+
+                // read at most one byte past the limit, so going over it can be detected without
+                // decompressing the rest
+                let mut decompressed = vec![];
+                zstd::stream::read::Decoder::new(data)
+                    .map_err(Error::IoError)?
+                    .take((limit as u64).saturating_add(1))
+                    .read_to_end(&mut decompressed)
+                    .map_err(Error::IoError)?;
+                if decompressed.len() > limit {
+                    return Err(Error::DecompressionLimitExceeded);
+                }
+                Ok(Cow::Owned(decompressed))
+            }
+            #[cfg(not(feature = "zstd_sarc"))] {
+                Err(Error::ParseError(
+                    "ZSTD compression detected but zstd_sarc feature not enabled.".into()
+                ))
+            }
+        }
+        _ => Ok(Cow::Borrowed(data))
+    }
+}
+
+/// Decompress up to `depth` layers of compression, starting with `compression` (or the
+/// compression detected from the magic if `None`) and detecting each following layer from its
+/// magic. Errors if the data is still compressed after `depth` layers.
+fn decompress_layers(data: &[u8], compression: Option<Compression>, limit: usize, depth: usize)
+    -> Result<Cow<'_, [u8]>, Error>
+{
+    let is_compressed = |compression| matches!(compression, Compression::Yaz0 | Compression::Zstd);
+
+    let mut compression = compression.unwrap_or_else(|| SarcFile::peek_compression(data));
+    let mut data = Cow::Borrowed(data);
+    for _ in 0..depth {
+        if !is_compressed(compression) {
+            return Ok(data);
+        }
+        data = Cow::Owned(decompress_with_limit(&data, compression, limit)?.into_owned());
+        compression = SarcFile::peek_compression(&data);
+    }
+
+    if is_compressed(compression) {
+        return Err(Error::CompressionDepthExceeded(depth));
+    }
+    Ok(data)
+}
+
+/// Decompress yaz0 data. Unlike the `yaz0` crate's decoder this bounds-checks every back-reference
+/// and returns an error on malformed input instead of panicking.
 #[cfg(feature = "yaz0_sarc")]
-use yaz0::Yaz0Archive;
+fn yaz0_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    const HEADER_SIZE: usize = 0x10;
+    // a single code byte followed by eight 3-byte back-references can produce at most 0x888 bytes
+    const MAX_RATIO: usize = 0x88;
+
+    let truncated = || Error::ParseError("Yaz0 data ended unexpectedly".into());
+
+    if data.len() < HEADER_SIZE {
+        return Err(truncated());
+    }
+    let expected_size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    let mut input = data[HEADER_SIZE..].iter().copied();
+    let mut out = Vec::with_capacity(expected_size.min(data.len().saturating_mul(MAX_RATIO)));
+    while out.len() < expected_size {
+        let code_byte = input.next().ok_or_else(truncated)?;
+        for bit in (0..8).rev() {
+            if out.len() >= expected_size {
+                break;
+            }
+            if code_byte & (1 << bit) != 0 {
+                out.push(input.next().ok_or_else(truncated)?);
+            } else {
+                let byte1 = input.next().ok_or_else(truncated)? as usize;
+                let byte2 = input.next().ok_or_else(truncated)? as usize;
+                let dist = (((byte1 & 0xf) << 8) | byte2) + 1;
+                let len = match byte1 >> 4 {
+                    0 => input.next().ok_or_else(truncated)? as usize + 0x12,
+                    n => n + 2
+                };
+                if dist > out.len() {
+                    return Err(Error::ParseError("Yaz0 back-reference before start of data".into()));
+                }
+                for _ in 0..len {
+                    out.push(out[out.len() - dist]);
+                }
+            }
+        }
+    }
+    out.truncate(expected_size);
+
+    Ok(out)
+}
 
 impl SarcFile {
     /// Read a sarc file (with or without compression) from a file.
     ///
     /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    #[cfg(feature = "std")]
     pub fn read_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
-        Self::read(&std::fs::read(path.as_ref()).map_err(|e| Error::IoError(e))?)
+        Self::read(&std::fs::read(path.as_ref()).map_err(Error::IoError)?)
+    }
+
+    /// Read a sarc file (with or without compression) from a file, also returning the
+    /// decompressed bytes of the sarc so they don't have to be read or decompressed again.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    #[cfg(feature = "std")]
+    pub fn read_from_file_with_raw<P: AsRef<std::path::Path>>(path: P)
+        -> Result<(Self, Vec<u8>), Error>
+    {
+        let data = std::fs::read(path.as_ref()).map_err(Error::IoError)?;
+        let raw = match decompress_if_needed(&data)? {
+            Cow::Owned(decompressed) => decompressed,
+            Cow::Borrowed(_) => data,
+        };
+        let sarc = RawSarc::parse(&raw)?.to_sarc_file()?;
+
+        Ok((sarc, raw))
+    }
+
+    /// Read a sarc file from a file, decompressing it with the given compression instead of
+    /// detecting it from the magic. Useful when the compression is known but the file doesn't
+    /// start with the usual magic.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features. If the
+    /// feature for `compression` isn't enabled this returns an error.
+    #[cfg(feature = "std")]
+    pub fn read_from_file_with_compression<P: AsRef<std::path::Path>>(
+        path: P,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        Self::read_with_compression(
+            &std::fs::read(path.as_ref()).map_err(Error::IoError)?,
+            compression
+        )
+    }
+
+    /// Read a sarc file from a byte slice, decompressing it with the given compression instead of
+    /// detecting it from the magic. Passing [`Compression::None`](Compression::None) or
+    /// [`Compression::Unknown`](Compression::Unknown) parses `data` as an uncompressed sarc file.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features. If the
+    /// feature for `compression` isn't enabled this returns an error.
+    pub fn read_with_compression(data: &[u8], compression: Compression) -> Result<Self, Error> {
+        let data = decompress_with(data, compression)?;
+        RawSarc::parse(&data)?.to_sarc_file()
+    }
+
+    /// Detect the compression of a sarc file from its magic, without decompressing or parsing it.
+    /// Returns [`Compression::Unknown`](Compression::Unknown) if `data` is shorter than 4 bytes or
+    /// doesn't start with a known magic.
+    pub fn peek_compression(data: &[u8]) -> Compression {
+        match data.get(..4) {
+            Some(magic) if magic == SARC_MAGIC => Compression::None,
+            Some(magic) if magic == YAZ0_MAGIC => Compression::Yaz0,
+            Some(magic) if magic == ZSTD_MAGIC => Compression::Zstd,
+            _ => Compression::Unknown,
+        }
     }
 
     /// Read a sarc file (with or without compression) from a byte slice.
     ///
     /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
     pub fn read(data: &[u8]) -> Result<Self, Error> {
-        let mut decompressed: Vec<u8>;
-        if data.len() < 4 {
-            return Err(Error::ParseError("Input buffer must be at least 4 bytes".into()));
+        Self::try_read(data)
+    }
+
+    /// Read a sarc file (with or without compression) embedded in a larger buffer, starting at
+    /// `offset`. All offsets within the sarc file are relative to its start, so anything after the
+    /// archive is ignored.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn read_at(data: &[u8], offset: usize) -> Result<Self, Error> {
+        let data = data.get(offset..)
+            .ok_or_else(|| Error::ParseError("Offset is past the end of the input buffer".into()))?;
+        Self::read(data)
+    }
+
+    /// Read an uncompressed sarc file from a byte slice, skipping compression detection. Useful
+    /// when the data is already known to be decompressed, such as the output of
+    /// [`decompress`](decompress). Returns an error if the data isn't an uncompressed sarc file,
+    /// including if it's compressed.
+    pub fn read_uncompressed(data: &[u8]) -> Result<Self, Error> {
+        RawSarc::parse(data)?.to_sarc_file()
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice, returning
+    /// [`Error::DecompressionLimitExceeded`](Error::DecompressionLimitExceeded) instead of
+    /// decompressing more than `max_decompressed` bytes. Use this for untrusted input, where a
+    /// small compressed file could otherwise decompress to enough data to exhaust memory.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn read_with_limit(data: &[u8], max_decompressed: usize) -> Result<Self, Error> {
+        let data = decompress_if_needed_with_limit(data, max_decompressed)?;
+        RawSarc::parse(&data)?.to_sarc_file()
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice with the given
+    /// [`ReadOptions`](ReadOptions). [`read`](SarcFile::read) is the same as this with the
+    /// default options.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn read_with_options(data: &[u8], options: &ReadOptions) -> Result<Self, Error> {
+        let data = decompress_layers(
+            data,
+            options.compression,
+            options.max_decompressed,
+            options.max_compression_depth
+        )?;
+        RawSarc::parse_with(&data, &options.magics)?.to_sarc_file_with(options)
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice, guaranteeing that
+    /// malformed input results in an `Err` rather than a panic. This is the same as
+    /// [`read`](SarcFile::read), but the guarantee is part of its contract and is checked by the
+    /// fuzz target in `fuzz/`, so it is the one to use for untrusted input.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn try_read(data: &[u8]) -> Result<Self, Error> {
+        let data = decompress_if_needed(data)?;
+        RawSarc::parse(&data)?.to_sarc_file()
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice, along with any bytes
+    /// after the end of the archive as given by the file size in its header, such as an appended
+    /// signature or footer. Standard archives have no trailer, in which case it is empty.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features. For
+    /// compressed archives the trailer is taken from the decompressed data.
+    pub fn read_with_trailer(data: &[u8]) -> Result<(Self, Vec<u8>), Error> {
+        let data = decompress_if_needed(data)?;
+        let raw = RawSarc::parse(&data)?;
+        let trailer = data.get(raw.header.file_size as usize..).unwrap_or_default();
+
+        Ok((raw.to_sarc_file()?, trailer.to_vec()))
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice, skipping files whose
+    /// name or data can't be read instead of failing. A warning is returned for each skipped
+    /// file, so data can be salvaged from an archive where only some files are damaged.
+    ///
+    /// **Note:** The returned archive may be incomplete, so check the warnings before trusting
+    /// it, and don't write it back over the original. An archive whose header, SFAT or SFNT can't
+    /// be read still fails entirely, as there's no way to tell where its files are.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn read_lossy(data: &[u8]) -> Result<(Self, Vec<ParseWarning>), Error> {
+        let data = decompress_if_needed(data)?;
+        RawSarc::parse(&data)?.to_sarc_file_lossy()
+    }
+
+    /// Read a sarc file (with or without compression) from a byte slice, along with the raw SFAT
+    /// node of each file. The metadata is in the same order as `files`, which is SFAT order.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn read_with_metadata(data: &[u8]) -> Result<(Self, Vec<SfatMeta>), Error> {
+        let data = decompress_if_needed(data)?;
+        let sarc = RawSarc::parse(&data)?;
+        let meta = sarc.nodes
+            .iter()
+            .map(|node| SfatMeta {
+                raw_attr: node.attrs,
+                hash: node.hash,
+                data_range: node.file_range.clone()
+            })
+            .collect();
+
+        Ok((sarc.to_sarc_file()?, meta))
+    }
+
+    /// Iterate over the files in the order their data appears in the data section, using the
+    /// metadata returned alongside this archive by
+    /// [`read_with_metadata`](SarcFile::read_with_metadata). Files with the same start offset
+    /// stay in SFAT order.
+    ///
+    /// **Note:** `meta` must be the metadata read with this archive, before any files were added,
+    /// removed or reordered; each file is paired with the metadata at the same index.
+    pub fn iter_by_offset<'a>(&'a self, meta: &'a [SfatMeta])
+        -> impl Iterator<Item = (&'a SarcEntry, &'a SfatMeta)>
+    {
+        let mut files: Vec<_> = self.files.iter().zip(meta).collect();
+        files.sort_by_key(|(_, meta)| meta.data_range.start);
+        files.into_iter()
+    }
+
+    /// List the files in a sarc file (with or without compression) without copying any of their
+    /// data, parsing only the header, SFAT and string table. The entries are in SFAT order, the
+    /// same order [`read`](SarcFile::read) gives `files` in.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn list_entries(data: &[u8]) -> Result<Vec<EntryInfo>, Error> {
+        let data = decompress_if_needed(data)?;
+        let sarc = RawSarc::parse(&data)?;
+
+        sarc.nodes
+            .iter()
+            .map(|node| {
+                // check the range the same way reading the file's data would
+                sarc.data(node)?;
+                Ok(EntryInfo {
+                    name: sarc.name(node)?.map(|name| String::from_utf8_lossy(name).into_owned()),
+                    hash: node.hash,
+                    range: node.file_range.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Read the files of an uncompressed sarc file without copying them, borrowing each file's
+    /// name and data from `data` instead. Names that aren't valid UTF-8 are kept as
+    /// [`raw_name`](SarcEntryRef::raw_name), nameless files keep their hash, and each file's
+    /// alignment is inferred from its offset as [`read`](SarcFile::read) does. The entries are in
+    /// SFAT order, the same order `read` gives `files` in.
+    ///
+    /// **Note:** Compressed archives can't be borrowed from, so they're rejected. Decompress them
+    /// first with [`decompress`](crate::decompress).
+    pub fn read_entries(data: &[u8]) -> Result<Vec<SarcEntryRef<'_>>, Error> {
+        if SarcFile::peek_compression(data) != Compression::None {
+            return Err(Error::ParseError("Expected an uncompressed sarc file".into()));
         }
-        let data = {
-            if b"Yaz0" == &data[..4] {
-                #[cfg(feature = "yaz0_sarc")] {
-                    let mut yaz0_reader = Yaz0Archive::new(Cursor::new(data)).map_err(|e| Error::Yaz0Error(e))?;
-                    decompressed = yaz0_reader.decompress().map_err(|e| Error::Yaz0Error(e))?;
-                    &decompressed
-                }
-                #[cfg(not(feature = "yaz0_sarc"))] {
-                    return Err(Error::ParseError(
-                        "Yaz0 compression detected but yaz0_sarc feature not enabled.".into()
-                    ));
-                } 
-            } else if b"\x28\xB5\x2F\xFD" == &data[..4] {  
-                #[cfg(feature = "zstd_sarc")] {
-                    decompressed = vec![];
-                    zstd::stream::copy_decode(
-                        std::io::Cursor::new(data),
-                        &mut decompressed
-                    ).map_err(|e| Error::IoError(e))?;
-                    &decompressed
-                }
-                #[cfg(not(feature = "zstd_sarc"))] {
-                    return Err(Error::ParseError(
-                        "ZSTD compression detected but zstd_sarc feature not enabled.".into()
-                    ));
-                } 
-            } else {
-                data
+        let sarc = RawSarc::parse(data)?;
+
+        sarc.nodes.iter().map(|node| sarc.entry_ref(node)).collect()
+    }
+
+    /// Extract the data of a single file from a sarc file (with or without compression) without
+    /// copying the data of any other file in the archive. Returns `Ok(None)` if no file with the
+    /// given name exists.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn extract_one(data: &[u8], name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let data = decompress_if_needed(data)?;
+        let sarc = RawSarc::parse(&data)?;
+        let hash = sfat_hash(name);
+
+        for node in sarc.nodes.iter().filter(|node| node.hash == hash) {
+            if sarc.name(node)? == Some(name.as_bytes()) {
+                return sarc.data(node).map(Vec::from).map(Some);
             }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Try to recover the names of every nameless file in a sarc file (with or without compression)
+/// by matching their hashes against a list of candidate names using
+/// [`sfat_reverse`](crate::sfat_reverse). Returns the hash of each nameless file, in SFAT order,
+/// along with the matching candidate if one was found.
+///
+/// Nameless files only keep their hash in the raw archive, which is why this takes the bytes of
+/// the archive rather than a [`SarcFile`](SarcFile).
+///
+/// **Note:** This is best-effort, see [`sfat_reverse`](crate::sfat_reverse) for details.
+pub fn sfat_reverse_all<'c>(data: &[u8], candidates: &[&'c str])
+    -> Result<Vec<(u32, Option<&'c str>)>, Error>
+{
+    let data = decompress_if_needed(data)?;
+    let sarc = RawSarc::parse(&data)?;
+
+    Ok(
+        sarc.nodes
+            .iter()
+            .filter(|node| node.name_offset.is_none())
+            .map(|node| (node.hash, sfat_reverse(node.hash, candidates)))
+            .collect()
+    )
+}
+
+impl TryFrom<&[u8]> for SarcFile {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Error> {
+        SarcFile::read(data)
+    }
+}
+
+impl TryFrom<Vec<u8>> for SarcFile {
+    type Error = Error;
+
+    fn try_from(data: Vec<u8>) -> Result<Self, Error> {
+        SarcFile::read(&data)
+    }
+}
+
+impl SarcEntry {
+    /// Parse the file as a nested sarc file (with or without compression). Returns `None` if the
+    /// file isn't a sarc file, and `Some(Err(_))` if it looks like one but fails to parse.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn as_sarc(&self) -> Option<Result<SarcFile, Error>> {
+        if SarcFile::peek_compression(&self.data) == Compression::Unknown {
+            return None
+        }
+
+        // compressed files only count as archives if they decompress to one
+        let data = match decompress_if_needed(&self.data) {
+            Ok(data) => data,
+            Err(err) => return Some(Err(err)),
         };
-        Self::parse(data)
-            .map(|a| a.1)
-            .map_err(|err| Error::ParseError(err.to_string()))
+        if !data.starts_with(&SARC_MAGIC) {
+            return None
+        }
+
+        Some(SarcFile::read(&data))
     }
 
-    fn parse(data: &[u8]) -> IResult<&[u8], Self> {
-        let (after_header, SarcHeader {
-            byte_order,
-            file_size: _,
-            data_offset
-        }) = SarcHeader::parse(data)?;
+    /// Check if the file's data starts with a yaz0 or zstd magic, meaning it's a nested compressed
+    /// file such as a `.szs` inside an archive
+    pub fn is_compressed(&self) -> bool {
+        matches!(SarcFile::peek_compression(&self.data), Compression::Yaz0 | Compression::Zstd)
+    }
 
-        let file_data = &data[data_offset as usize..];
+    /// Get the file's data, decompressing it if it [`is_compressed`](SarcEntry::is_compressed).
+    /// Uncompressed data is borrowed rather than copied. Only one layer of compression is removed.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn decompressed_data(&self) -> Result<Cow<'_, [u8]>, Error> {
+        if self.is_compressed() {
+            decompress_with(&self.data, SarcFile::peek_compression(&self.data))
+        } else {
+            Ok(Cow::Borrowed(&self.data))
+        }
+    }
+}
 
-        let (data, (_, files)) = match byte_order {
-            Endian::Big => parse_sfat::<BigEndian>(after_header)?,
-            Endian::Little => parse_sfat::<LittleEndian>(after_header)?
+impl SarcFile {
+    /// Recursively expand every nested sarc file into its contents, producing a single flat
+    /// archive. Files inside a nested archive are named `{outer name}/{inner name}`, so a file
+    /// `Model/foo.bfres` inside `Actor/Pack/foo.sbactorpack` becomes
+    /// `Actor/Pack/foo.sbactorpack/Model/foo.bfres`.
+    ///
+    /// Nested archives are only expanded up to `max_depth` levels deep, beyond that (and for
+    /// nameless or unparsable nested archives) the archive is kept as a single file.
+    ///
+    /// **Note:** Nested compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn flatten(&self, max_depth: usize) -> SarcFile {
+        SarcFile {
+            byte_order: self.byte_order,
+            version: self.version,
+            hash_key: self.hash_key,
+            data_alignment: self.data_alignment,
+            // flattening changes the layout, so the header values no longer apply
+            data_offset: None,
+            file_size: None,
+            files: flatten_files(&self.files, max_depth)
+        }
+    }
+}
+
+fn flatten_files(files: &[SarcEntry], max_depth: usize) -> Vec<SarcEntry> {
+    let mut flattened = vec![];
+    for file in files {
+        let nested = match (&file.name, max_depth) {
+            (Some(name), 1..) => file.as_sarc().and_then(Result::ok).map(|sarc| (name, sarc)),
+            _ => None
         };
 
-        let string_data = &data[0x8..];
+        match nested {
+            Some((outer_name, sarc)) => {
+                let inner = flatten_files(&sarc.files, max_depth - 1);
+                flattened.extend(inner.into_iter().map(|mut entry| {
+                    if let Some(name) = &entry.name {
+                        entry.name = Some(format!("{}/{}", outer_name, name));
+                    }
+                    entry
+                }));
+            }
+            None => flattened.push(file.clone())
+        }
+    }
 
-        let files: Vec<_> =
-            files.into_iter()
-                .map(|SfatNode { name_offset, file_range }| {
-                    let name = name_offset.map(
-                        |off| get_string(string_data, (off as usize) * 4)
-                    ).flatten();
-                    let data = Vec::from(&file_data[file_range]);
+    flattened
+}
 
-                    SarcEntry { name, data }
-                })
-                .collect();
+/// Everything about an uncompressed sarc file except its data, as read by
+/// [`read_metadata`](read_metadata)
+#[cfg(feature = "std")]
+pub(crate) struct Metadata {
+    pub(crate) byte_order: Endian,
+    pub(crate) version: u16,
+    /// Alignment of the data section, inferred from its offset
+    pub(crate) data_alignment: Alignment,
+    /// Length of the data section, including any padding at the end of the file
+    pub(crate) data_len: usize,
+    /// The name, hash and data range of each file, in SFAT order
+    pub(crate) files: Vec<(Option<Vec<u8>>, u32, Range<usize>)>,
+}
 
+/// Read everything in front of the data section of an uncompressed sarc file, leaving `reader`
+/// positioned at the start of the data section.
+#[cfg(feature = "std")]
+pub(crate) fn read_metadata<R: std::io::Read>(reader: &mut R) -> Result<Metadata, Error> {
+    let mut data = vec![0; SARC_HEADER_SIZE as usize];
+    reader.read_exact(&mut data).map_err(Error::IoError)?;
+    if SarcFile::peek_compression(&data) != Compression::None {
+        return Err(Error::ParseError("Expected an uncompressed sarc file".into()));
+    }
 
-        Ok((data, SarcFile {
-            byte_order,
+    let (_, header) = SarcHeader::parse(&data)?;
+    if header.data_offset < SARC_HEADER_SIZE as u32 || header.data_offset > header.file_size {
+        return Err(Error::DataOffsetOutOfBounds(header.data_offset));
+    }
+    data.resize(header.data_offset as usize, 0);
+    reader.read_exact(&mut data[SARC_HEADER_SIZE as usize..]).map_err(Error::IoError)?;
+
+    let raw = RawSarc::parse(&data)?;
+    let data_len = (header.file_size - header.data_offset) as usize;
+    let files = raw.nodes
+        .iter()
+        .map(|node| {
+            if node.file_range.end > data_len {
+                return Err(Error::ParseError("File data range out of bounds".into()));
+            }
+            Ok((raw.name(node)?.map(Vec::from), node.hash, node.file_range.clone()))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Metadata {
+        byte_order: header.byte_order,
+        version: header.version,
+        data_alignment: inferred_alignment(header.data_offset as usize),
+        data_len,
+        files,
+    })
+}
+
+/// Get the largest power of two, up to [`Alignment::DATA`](Alignment::DATA), that `offset` is a
+/// multiple of
+fn inferred_alignment(offset: usize) -> Alignment {
+    let max = Alignment::DATA.get();
+    match offset {
+        0 => Alignment::DATA,
+        _ => Alignment((1u32 << offset.trailing_zeros().min(31)).min(max)),
+    }
+}
+
+fn nom_error(err: nom::Err<nom::error::Error<&[u8]>>) -> Error {
+    Error::ParseError(err.to_string())
+}
+
+/// A file in an archive as listed by [`list_entries`](SarcFile::list_entries)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// The name of the file, with any invalid UTF-8 replaced by U+FFFD
+    pub name: Option<String>,
+    /// The hash of the file's name, as stored in the SFAT
+    pub hash: u32,
+    /// The range of the file's data, relative to the start of the data section
+    pub range: Range<usize>,
+}
+
+/// A file skipped by [`read_lossy`](SarcFile::read_lossy) because it couldn't be read
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// The index of the file's node in the SFAT
+    pub index: usize,
+    /// Why the file couldn't be read
+    pub reason: Error,
+}
+
+/// The raw SFAT node of a file, as returned by
+/// [`read_with_metadata`](SarcFile::read_with_metadata)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SfatMeta {
+    /// The raw attributes of the node. The top byte is 1 if the file has a name (or the collision
+    /// count for files whose names share a hash), and the rest is the offset of the name in the
+    /// string table divided by 4.
+    pub raw_attr: u32,
+    /// The hash of the file's name
+    pub hash: u32,
+    /// The range of the file's data, relative to the start of the data section
+    pub data_range: Range<usize>,
+}
+
+/// The sections of a parsed sarc file, before any names or file data have been copied out
+struct RawSarc<'a> {
+    header: SarcHeader,
+    hash_key: u32,
+    nodes: Vec<SfatNode>,
+    string_table: &'a [u8],
+    file_data: &'a [u8],
+}
+
+impl<'a> RawSarc<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        Self::parse_with(data, &Magics::default())
+    }
+
+    fn parse_with(data: &'a [u8], magics: &Magics) -> Result<Self, Error> {
+        let (_, header) = SarcHeader::parse_with(data, &magics.sarc)?;
+        header.validate(data)?;
+        debug!(
+            byte_order = ?header.byte_order,
+            version = header.version,
+            file_size = header.file_size,
+            data_offset = header.data_offset,
+            "parsed SARC header"
+        );
+
+        // the SFAT and SFNT sit between the header and the data, which validate checked is in bounds
+        let sections = &data[SARC_HEADER_SIZE as usize..header.data_offset as usize];
+        // anything past the file size, such as padding after the archive, isn't file data
+        let file_end = (header.file_size as usize).min(data.len());
+        let file_data = &data[header.data_offset as usize..file_end];
+
+        let (after_sfat, hash_key, nodes) = match header.byte_order {
+            Endian::Big => parse_sfat::<BigEndian>(sections, &magics.sfat),
+            Endian::Little => parse_sfat::<LittleEndian>(sections, &magics.sfat)
+        }?;
+
+        let (after_sfnt, _) = match header.byte_order {
+            Endian::Big => parse_sfnt::<BigEndian>(after_sfat, &magics.sfnt),
+            Endian::Little => parse_sfnt::<LittleEndian>(after_sfat, &magics.sfnt)
+        }.map_err(|_| Error::MalformedStringTable)?;
+
+        // the string table spans from the end of the SFNT header up to the start of the data
+        let string_table = after_sfnt;
+        debug!(len = string_table.len(), "found string table");
+
+        Ok(Self { header, hash_key, nodes, string_table, file_data })
+    }
+
+    fn to_sarc_file(&self) -> Result<SarcFile, Error> {
+        self.to_sarc_file_with(&ReadOptions::default())
+    }
+
+    fn to_sarc_file_with(&self, options: &ReadOptions) -> Result<SarcFile, Error> {
+        // collecting into a Result can't use the iterator's length as a capacity hint, so
+        // allocate up front and push instead
+        let mut files = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            files.push(self.entry(node, options)?);
+        }
+
+        Ok(self.sarc_file(files))
+    }
+
+    /// Convert to a [`SarcFile`](SarcFile), skipping the files that can't be read and returning
+    /// a warning for each
+    fn to_sarc_file_lossy(&self) -> Result<(SarcFile, Vec<ParseWarning>), Error> {
+        let options = ReadOptions::default();
+        let mut files = Vec::with_capacity(self.nodes.len());
+        let mut warnings = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            match self.entry(node, &options) {
+                Ok(file) => files.push(file),
+                Err(reason) => warnings.push(ParseWarning { index, reason }),
+            }
+        }
+
+        Ok((self.sarc_file(files), warnings))
+    }
+
+    fn sarc_file(&self, files: Vec<SarcEntry>) -> SarcFile {
+        SarcFile {
+            byte_order: self.header.byte_order,
+            version: self.header.version,
+            hash_key: self.hash_key,
+            data_alignment: inferred_alignment(self.header.data_offset as usize),
+            data_offset: Some(self.header.data_offset),
+            file_size: Some(self.header.file_size),
             files
-        }))
+        }
+    }
+
+    /// Copy the name and data of a node out into a [`SarcEntry`](SarcEntry)
+    fn entry(&self, node: &SfatNode, options: &ReadOptions) -> Result<SarcEntry, Error> {
+        let name_bytes = self.name(node)?;
+        let name = name_bytes.and_then(|name| core::str::from_utf8(name).ok());
+        let raw_name = name_bytes.filter(|_| name.is_none() && options.keep_raw_names);
+
+        Ok(SarcEntry {
+            name: name.map(String::from),
+            raw_name: raw_name.map(Vec::from),
+            hash: Some(node.hash).filter(|_| name.is_none() && raw_name.is_none()),
+            alignment: Some(inferred_alignment(node.file_range.start))
+                .filter(|_| options.preserve_layout),
+            data: Vec::from(self.data(node)?)
+        })
+    }
+
+    /// Borrow the name and data of a node as a [`SarcEntryRef`](SarcEntryRef)
+    fn entry_ref(&self, node: &SfatNode) -> Result<SarcEntryRef<'a>, Error> {
+        let name_bytes = self.name(node)?;
+        let name = name_bytes.and_then(|name| core::str::from_utf8(name).ok());
+
+        Ok(SarcEntryRef {
+            name,
+            raw_name: name_bytes.filter(|_| name.is_none()),
+            hash: Some(node.hash).filter(|_| name_bytes.is_none()),
+            alignment: Some(inferred_alignment(node.file_range.start)),
+            data: self.data(node)?
+        })
+    }
+
+    /// Get the name of a node from the string table. Named nodes whose name offset lies outside
+    /// the string table or which aren't NUL-terminated are treated as malformed.
+    fn name(&self, node: &SfatNode) -> Result<Option<&'a [u8]>, Error> {
+        match node.name_offset {
+            Some(off) => {
+                let name = get_string(self.string_table, (off as usize) * 4);
+                trace!(
+                    hash = node.hash,
+                    offset = (off as usize) * 4,
+                    name = ?name.map(String::from_utf8_lossy),
+                    "resolved name"
+                );
+                name.map(Some).ok_or(Error::MalformedStringTable)
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn data(&self, node: &SfatNode) -> Result<&'a [u8], Error> {
+        self.file_data
+            .get(node.file_range.clone())
+            .ok_or_else(|| Error::ParseError("File data range out of bounds".into()))
     }
 }
 
 impl SarcHeader {
-    fn parse(data: &[u8]) -> IResult<&[u8], Self> {
+    /// Check the header parsed from `data` for values that parse fine but that this crate can't handle
+    fn validate(&self, data: &[u8]) -> Result<(), Error> {
+        if self.header_size != SARC_HEADER_SIZE {
+            return Err(Error::BadHeaderSize(self.header_size));
+        }
+
+        // a data offset inside the header would make the header itself part of the file data
+        if self.data_offset < SARC_HEADER_SIZE as u32
+            || self.data_offset > self.file_size
+            || self.data_offset as usize > data.len()
+        {
+            return Err(Error::DataOffsetOutOfBounds(self.data_offset));
+        }
+
+        if self.version != SARC_VERSION {
+            return Err(Error::UnsupportedVersion(self.version));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn parse(data: &[u8]) -> Result<(&[u8], Self), Error> {
+        Self::parse_with(data, &SARC_MAGIC)
+    }
+
+    fn parse_with<'a>(data: &'a [u8], magic: &[u8; 4]) -> Result<(&'a [u8], Self), Error> {
+        // the header size comes before the BOM, so it's kept as raw bytes until the byte order
+        // of the rest of the header is known
         let (data, (
             _,
-            _,
-            endian,
-        )) = tuple::<_, _, NE, _>((
-            tag(b"SARC"),
-            le_u16,
-            be_u16,
-        ))(data).unwrap();
-
-        match endian.into() {
-            Endian::Big => Self::parse_endian::<BigEndian>(data, Endian::Big),
-            Endian::Little => Self::parse_endian::<LittleEndian>(data, Endian::Little)
-        }
+            header_size,
+            bom,
+        )) = tuple((
+            tag(&magic[..]),
+            take(2usize),
+            take_u16::<BigEndian>,
+        ))(data).map_err(nom_error)?;
+
+        match Endian::from_bom(bom)? {
+            Endian::Big => Self::parse_endian::<BigEndian>(data, header_size, Endian::Big),
+            Endian::Little => Self::parse_endian::<LittleEndian>(data, header_size, Endian::Little),
+        }.map_err(nom_error)
     }
 
-    fn parse_endian<E: TakeEndian>(data: &[u8], byte_order: Endian) -> IResult<&[u8], Self> {
+    fn parse_endian<'a, E: TakeEndian>(data: &'a [u8], header_size: &'a [u8], byte_order: Endian)
+        -> IResult<&'a [u8], Self>
+    {
+        let (_, header_size) = take_u16::<E>(header_size)?;
         let (data, (
             file_size,
             data_offset,
+            version,
             _
         )) = tuple((
             take_u32::<E>,
             take_u32::<E>,
-            take_u32::<E>
+            take_u16::<E>,
+            take_u16::<E>
         ))(data)?;
 
         Ok((data, Self {
+            header_size,
             byte_order,
             file_size,
-            data_offset
+            data_offset,
+            version
         }))
     }
 }
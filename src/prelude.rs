@@ -0,0 +1,10 @@
+//! Re-exports of the types needed for typical use of this crate.
+//!
+//! ```rust
+//! use sarc::prelude::*;
+//! ```
+pub use crate::{SarcFile, SarcEntry, Endian, Compression};
+pub use crate::parser::Error as ReadError;
+#[cfg(feature = "std")]
+pub use crate::writer::Error as WriteError;
+pub use crate::merge::Error as MergeError;
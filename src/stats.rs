@@ -0,0 +1,62 @@
+use super::{SarcFile, SarcEntry, Alignment};
+use alloc::{collections::BTreeMap, string::String};
+use core::fmt;
+
+/// A summary of the contents of an archive, as returned by [`SarcFile::stats`](SarcFile::stats)
+#[derive(Debug, PartialEq, Eq)]
+pub struct SarcStats<'a> {
+    /// Number of files in the archive, including nameless files
+    pub file_count: usize,
+    /// Number of files in the archive with a name
+    pub named_count: usize,
+    /// Total size of the data of every file, not including padding
+    pub total_size: usize,
+    /// The file with the most data, or `None` if the archive is empty. The first such file wins
+    /// ties.
+    pub largest_file: Option<&'a SarcEntry>,
+    /// Number of files with each alignment. Files with no alignment (which is only the case for
+    /// files that weren't read from an archive) aren't counted.
+    pub alignment_histogram: BTreeMap<Alignment, usize>,
+}
+
+impl SarcFile {
+    /// Summarize the contents of the archive
+    pub fn stats(&self) -> SarcStats<'_> {
+        let mut alignment_histogram = BTreeMap::new();
+        for alignment in self.files.iter().filter_map(|file| file.alignment) {
+            *alignment_histogram.entry(alignment).or_insert(0) += 1;
+        }
+
+        SarcStats {
+            file_count: self.len(),
+            named_count: self.files.iter().filter(|file| file.name_bytes().is_some()).count(),
+            total_size: self.total_data_size(),
+            largest_file: self.files.iter()
+                .rev()
+                .max_by_key(|file| file.data.len()),
+            alignment_histogram,
+        }
+    }
+}
+
+impl fmt::Display for SarcStats<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "files: {} ({} named)", self.file_count, self.named_count)?;
+        writeln!(f, "total size: {:#x} bytes", self.total_size)?;
+        if let Some(file) = self.largest_file {
+            match file.name_bytes() {
+                Some(name) => write!(f, "largest file: {}", String::from_utf8_lossy(name))?,
+                None => write!(f, "largest file: <nameless {:#010x}>", file.sfat_hash())?,
+            }
+            writeln!(f, " ({:#x} bytes)", file.data.len())?;
+        }
+        if !self.alignment_histogram.is_empty() {
+            writeln!(f, "alignment:")?;
+            for (alignment, count) in &self.alignment_histogram {
+                writeln!(f, "  {:#x}: {}", alignment.get(), count)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+//! Generators for synthetic archives, used by the benchmarks and tests. Not part of the stable
+//! API.
+use crate::{SarcFile, SarcEntry, Endian, Alignment, SARC_VERSION, HASH_KEY};
+use alloc::{format, vec::Vec};
+
+/// Generate an archive of `num_files` files named `file/{i}.bin`, each `file_size` bytes long and
+/// filled with a byte pattern that differs between files so compression has some work to do.
+pub fn make_archive(num_files: usize, file_size: usize) -> SarcFile {
+    SarcFile {
+        byte_order: Endian::Little,
+        version: SARC_VERSION,
+        hash_key: HASH_KEY,
+        data_alignment: Alignment::DATA,
+        data_offset: None,
+        file_size: None,
+        files: (0..num_files)
+            .map(|i| {
+                let data: Vec<u8> = (0..file_size).map(|j| (i + j / 16) as u8).collect();
+                SarcEntry::new(format!("file/{}.bin", i), data)
+            })
+            .collect()
+    }
+}
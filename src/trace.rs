@@ -0,0 +1,18 @@
+//! Instrumentation for debugging unusual archives, enabled by the `tracing` feature. Without the
+//! feature the macros expand to nothing, so their arguments aren't even evaluated.
+
+/// Emit a `tracing` event at debug level, for summaries such as a parsed header or node count
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!($($arg)*);
+    };
+}
+
+/// Emit a `tracing` event at trace level, for single items such as an SFAT node or name lookup
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)*);
+    };
+}
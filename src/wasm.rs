@@ -0,0 +1,73 @@
+//! [`wasm-bindgen`](https://docs.rs/wasm-bindgen) bindings for working with archives from
+//! JavaScript, such as in a browser-based archive explorer. Requires the `wasm` feature.
+//!
+//! Archives are passed in and out as bytes (a `Uint8Array` on the JavaScript side), with or
+//! without compression, and every function fails with the same [`Error`](Error), thrown as a
+//! JavaScript `Error`.
+use super::{SarcFile, SarcEntry, Compression, parser, writer};
+use wasm_bindgen::prelude::*;
+
+/// An error from one of the bindings, thrown to JavaScript as an `Error` with its debug output as
+/// the message
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The archive couldn't be read
+    ReadError(parser::Error),
+    /// The archive couldn't be written
+    WriteError(writer::Error),
+}
+
+impl From<parser::Error> for Error {
+    fn from(err: parser::Error) -> Self {
+        Self::ReadError(err)
+    }
+}
+
+impl From<writer::Error> for Error {
+    fn from(err: writer::Error) -> Self {
+        Self::WriteError(err)
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsError::new(&format!("{:?}", err)).into()
+    }
+}
+
+/// List the names of the files in an archive, in SFAT order. Nameless files are skipped, as they
+/// can't be extracted by name.
+#[wasm_bindgen]
+pub fn list(data: &[u8]) -> Result<Vec<String>, Error> {
+    Ok(
+        SarcFile::list_entries(data)?
+            .into_iter()
+            .filter_map(|entry| entry.name)
+            .collect()
+    )
+}
+
+/// Extract the data of the file with the given name from an archive, returning `undefined` if no
+/// such file exists. See [`SarcFile::extract_one`](SarcFile::extract_one).
+#[wasm_bindgen]
+pub fn extract(data: &[u8], name: &str) -> Result<Option<Vec<u8>>, Error> {
+    Ok(SarcFile::extract_one(data, name)?)
+}
+
+/// Repack an archive with the file `name` set to `contents`, replacing the file if it exists and
+/// adding it otherwise. The archive is written back with the compression it was read with.
+#[wasm_bindgen]
+pub fn repack(data: &[u8], name: &str, contents: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let mut sarc = SarcFile::read(data)?;
+    match sarc.get_mut(name) {
+        Some(file) => file.set_data(contents),
+        None => sarc.files.push(SarcEntry::new(name, contents)),
+    }
+
+    let out = sarc.write_to_vec()?;
+    match SarcFile::peek_compression(data) {
+        Compression::None => Ok(out),
+        compression => Ok(writer::compress(&out, compression)?),
+    }
+}
@@ -1,19 +1,107 @@
+//! Writing sarc files. Requires the `std` feature, as writing is built on `std::io::Write`.
 use binwrite::{BinWrite, writer_option_new};
 use super::*;
 use std::io::prelude::*;
 use std::io::BufWriter;
 use std::path::Path;
+use std::convert::TryFrom;
 use std::collections::HashMap;
+use lowlevel::{SarcHeader, SfatEntry, Sfat, SfntHeader, number_collisions};
+
+pub mod lowlevel;
 
 /// An error raised in the process of writing the sarc file
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     IoError(std::io::Error),
 
+    /// The archive would be larger than the 4 GiB a SARC header can describe
+    ArchiveTooLarge(u64),
+
+    /// The archive has more files than the 65535 an SFAT can hold
+    TooManyFiles(usize),
+
+    /// More than one file in the archive has the given name
+    DuplicateName(String),
+
+    /// An existing archive being modified couldn't be read
+    ReadError(crate::parser::Error),
+
+    /// The requested compression can't be written, either because the feature for it isn't
+    /// enabled or because it is [`Compression::Unknown`](Compression::Unknown)
+    UnsupportedCompression(Compression),
+
     #[cfg(feature = "yaz0_sarc")]
     Yaz0Error(yaz0::Error),
 }
 
+/// Order of the names in the string table of a written sarc file. The SFAT is always sorted by
+/// hash regardless of this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringOrder {
+    /// Sort names by hash, matching the SFAT. This is what most archives use.
+    #[default]
+    Hash,
+    /// Keep names in the order of [`files`](SarcFile::files), which some archives use
+    Files,
+}
+
+/// Order of files with the same hash in the data section of a written sarc file. The SFAT and
+/// string table always break ties by name regardless of this, since each SFAT node points at its
+/// own data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataOrder {
+    /// Order by name, with nameless files first, and then by position in
+    /// [`files`](SarcFile::files)
+    #[default]
+    Name,
+    /// Keep the order of [`files`](SarcFile::files), matching tools that don't sort ties
+    Files,
+    /// Order by size, smallest first, and then by position in [`files`](SarcFile::files).
+    /// Keeping files of similar size together can improve compression when many files share a
+    /// hash, such as in archives written without names.
+    Size,
+}
+
+/// Size of an archive before and after compression, as returned by
+/// [`write_yaz0_reporting`](SarcFile::write_yaz0_reporting) and
+/// [`write_zstd_reporting`](SarcFile::write_zstd_reporting)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressStats {
+    /// Size of the archive before compression, in bytes
+    pub uncompressed: usize,
+    /// Size of the archive after compression, in bytes
+    pub compressed: usize,
+}
+
+impl CompressStats {
+    /// Get the compressed size as a fraction of the uncompressed size
+    pub fn ratio(&self) -> f64 {
+        self.compressed as f64 / self.uncompressed as f64
+    }
+}
+
+impl From<Endian> for binwrite::Endian {
+    fn from(endian: Endian) -> Self {
+        match endian {
+            Endian::Big => binwrite::Endian::Big,
+            Endian::Little => binwrite::Endian::Little,
+        }
+    }
+}
+
+/// Convert from a `binwrite` byte order, resolving `Native` to [`Endian::native`](Endian::native)
+impl From<binwrite::Endian> for Endian {
+    fn from(endian: binwrite::Endian) -> Self {
+        match endian {
+            binwrite::Endian::Big => Endian::Big,
+            binwrite::Endian::Little => Endian::Little,
+            binwrite::Endian::Native => Endian::native(),
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         Self::IoError(e)
@@ -22,10 +110,22 @@ impl From<std::io::Error> for Error {
 
 impl SarcFile {
     /// Write 
-    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
         self.write(&mut BufWriter::new(std::fs::File::create(path.as_ref())?))
     }
 
+    /// Write to a file without ever leaving a partially written archive at `path`. The archive is
+    /// written to a temporary file next to `path` which then replaces it, so if writing fails
+    /// (or the program is interrupted) the original file is left untouched. The temporary file is
+    /// removed if writing fails. This writes the SARC with no compression.
+    ///
+    /// **Note:** If `path` is on a different device than its temporary file (such as when `path`
+    /// is a mount point itself) the file can't be renamed, so it is copied over instead, which
+    /// isn't atomic.
+    pub fn write_to_file_atomic<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write_atomic(path.as_ref(), |out| self.write(out))
+    }
+
     /// Write to a compressed file. This writes the SARC with yaz0 compression. Requires either the
     /// `yaz0_sarc` feature or `zstd_sarc` feature enabled.
     ///
@@ -57,219 +157,976 @@ impl SarcFile {
         self.write_yaz0(
             &mut std::fs::File::create(path.as_ref())
                 .map(BufWriter::new)
-                .map_err(|e| Error::IoError(e))?
+                .map_err(Error::IoError)?
         )
     }
 
     /// Write to a writer that implements [`std::io::Write`](std::io::Write). This writes the SARC with yaz0 
     /// compression. Requires `yaz0_sarc` feature.
+    ///
+    /// **Note:** The yaz0 compressor only accepts its input as a single slice, so the uncompressed
+    /// archive is serialized into a temporary buffer first. The buffer is allocated up front using
+    /// [`serialized_size`](SarcFile::serialized_size) so it is never reallocated while writing.
     #[cfg(feature = "yaz0_sarc")]
     pub fn write_yaz0<W: Write>(&self, f: &mut W) -> Result<(), Error> {
-        let writer = yaz0::Yaz0Writer::new(f);
-        let mut temp = vec![];
+        self.write_yaz0_reporting(f).map(|_| ())
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with yaz0
+    /// compression, returning the size of the archive before and after compression. Requires
+    /// `yaz0_sarc` feature.
+    #[cfg(feature = "yaz0_sarc")]
+    pub fn write_yaz0_reporting<W: Write>(&self, f: &mut W) -> Result<CompressStats, Error> {
+        let mut counter = CountingWriter::new(f);
+        let mut temp = Vec::with_capacity(self.serialized_size());
         self.write(&mut temp)?;
-        writer.compress_and_write(&temp, yaz0::CompressionLevel::Lookahead { quality: 10 })
-            .map_err(|e| Error::Yaz0Error(e))
+        compress_to(&mut counter, &temp, Compression::Yaz0)?;
+
+        Ok(CompressStats { uncompressed: temp.len(), compressed: counter.count })
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with yaz0
+    /// compression, calling `progress(processed, total)` as the archive is compressed, where
+    /// `processed` is how many of the `total` uncompressed bytes have been compressed so far.
+    /// Requires `yaz0_sarc` feature.
+    ///
+    /// The compression runs on a separate thread, but `progress` is only ever called from the
+    /// calling thread and never concurrently, so it doesn't need to be `Send` or `Sync`.
+    /// `processed` only ever increases, and the last call is always with `processed == total`.
+    #[cfg(feature = "yaz0_sarc")]
+    pub fn write_yaz0_with_progress<W, F>(&self, f: &mut W, mut progress: F) -> Result<(), Error>
+        where W: Write,
+              F: FnMut(usize, usize),
+    {
+        let mut temp = Vec::with_capacity(self.serialized_size());
+        self.write(&mut temp)?;
+        let total = temp.len();
+
+        let (tx, rx) = std::sync::mpsc::channel::<yaz0::deflate::ProgressMsg>();
+        let temp = &temp;
+        let compressed = std::thread::scope(|scope| {
+            let compressor = scope.spawn(move || {
+                let mut compressed = vec![];
+                yaz0::Yaz0Writer::new(&mut compressed)
+                    .compress_and_write_with_progress(temp, YAZ0_LEVEL, tx)
+                    .map(|_| compressed)
+            });
+
+            // the channel closes once the compressor finishes and drops its sender
+            let mut processed = 0;
+            for msg in rx {
+                if msg.read_head > processed {
+                    processed = msg.read_head.min(total);
+                    progress(processed, total);
+                }
+            }
+            if processed < total {
+                progress(total, total);
+            }
+
+            compressor.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        }).map_err(Error::Yaz0Error)?;
+
+        f.write_all(&compressed)?;
+        Ok(())
     }
 
     /// Write to a writer that implements [`std::io::Write`](std::io::Write). This writes the SARC with zstd
     /// compression. Requires `zstd_sarc` feature.
     #[cfg(feature = "zstd_sarc")]
     pub fn write_zstd<W: Write>(&self, f: &mut W) -> Result<(), Error> {
+        self.write_zstd_reporting(f).map(|_| ())
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with zstd
+    /// compression, returning the size of the archive before and after compression. Requires
+    /// `zstd_sarc` feature.
+    #[cfg(feature = "zstd_sarc")]
+    pub fn write_zstd_reporting<W: Write>(&self, f: &mut W) -> Result<CompressStats, Error> {
+        let mut counter = CountingWriter::new(f);
         let mut writer =
-            zstd::stream::Encoder::new(f, zstd::DEFAULT_COMPRESSION_LEVEL)?;
-        self.write(&mut writer)?;
-        writer.finish().unwrap();
+            zstd::stream::Encoder::new(&mut counter, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        let mut uncompressed = CountingWriter::new(&mut writer);
+        self.write(&mut uncompressed)?;
+        let uncompressed = uncompressed.count;
+        writer.finish()?;
+
+        Ok(CompressStats { uncompressed, compressed: counter.count })
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with zstd
+    /// compression, calling `progress(processed, total)` as the archive is compressed, where
+    /// `processed` is how many of the `total` uncompressed bytes have been compressed so far.
+    /// Requires `zstd_sarc` feature.
+    ///
+    /// The archive is compressed as it's written, so `progress` is called from the calling thread
+    /// after each write to the compressor. `processed` only ever increases, and the last call is
+    /// always with `processed == total`.
+    #[cfg(feature = "zstd_sarc")]
+    pub fn write_zstd_with_progress<W, F>(&self, f: &mut W, progress: F) -> Result<(), Error>
+        where W: Write,
+              F: FnMut(usize, usize),
+    {
+        let total = self.serialized_size();
+        let mut writer = zstd::stream::Encoder::new(f, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        self.write(&mut ProgressWriter { inner: &mut writer, processed: 0, total, progress })?;
+        writer.finish()?;
+
         Ok(())
     }
 
-    /// Write to a writer that implements [`std::io::Write`](std::io::Write). This writes the SARC with no 
-    /// compression.
-    pub fn write<W: Write>(&self, f: &mut W) -> std::io::Result<()> {
-        let (string_offsets, string_section) = self.generate_string_section();
-        let (data_offsets, data_section) = self.generate_data_section();
+    /// Write to a new `Vec<u8>`, preallocated using [`serialized_size`](SarcFile::serialized_size).
+    /// This writes the SARC with no compression.
+    pub fn write_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(self.serialized_size());
+        self.write(&mut data)?;
+        Ok(data)
+    }
 
-        let num_files = self.files.len();
-        let data_padding_offset = SarcHeader::SIZE + Sfat::HEADER_SIZE
-            + (num_files * SfatEntry::SIZE) + SFNT_HEADER_SIZE + string_section.len();
-        let data_offset = (data_padding_offset + 0x1FFF) & !0x1FFF;
-        let data_padding = data_offset - data_padding_offset;
+    /// Write to a new `Vec<u8>`. This writes the SARC with yaz0 compression. Requires `yaz0_sarc`
+    /// feature.
+    #[cfg(feature = "yaz0_sarc")]
+    pub fn write_yaz0_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        self.write_yaz0(&mut data)?;
+        Ok(data)
+    }
 
-        let file_size = (data_offset + data_section.len()) as u32;
-        let data_offset = data_offset as u32;
+    /// Write to a new `Vec<u8>`. This writes the SARC with zstd compression. Requires `zstd_sarc`
+    /// feature.
+    #[cfg(feature = "zstd_sarc")]
+    pub fn write_zstd_to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        self.write_zstd(&mut data)?;
+        Ok(data)
+    }
 
-        let options = &match self.byte_order {
-            Endian::Big => writer_option_new!(endian: binwrite::Endian::Big),
-            Endian::Little => writer_option_new!(endian: binwrite::Endian::Little)
-        };
+    /// Compress the data of the file with the given name in place, returning `false` if there is
+    /// no such file. [`Compression::None`](Compression::None) leaves the data unchanged. The data
+    /// is compressed even if it already is, so this can be used to nest compression on purpose.
+    ///
+    /// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features.
+    pub fn compress_entry(&mut self, name: &str, compression: Compression) -> Result<bool, Error> {
+        match self.get_mut(name) {
+            Some(file) => {
+                file.data = compress(&file.data, compression)?;
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
 
-        SarcHeader {
-            file_size,
-            data_offset
-        }.write_options(f, options)?;
+    /// Yaz0 compress every file whose extension marks it as compressed, returning how many files
+    /// were compressed. By Nintendo's convention an `s` in front of an extension means the file is
+    /// yaz0 compressed, such as `.sbfres` for a compressed `.bfres`, and `.szs` is a compressed
+    /// `.sarc`. Since plenty of uncompressed extensions also start with `s` (`.sarc` itself among
+    /// them), only the known compressed extensions in [`YAZ0_EXTENSIONS`](YAZ0_EXTENSIONS) are
+    /// matched. Files that are already compressed are left as they are.
+    ///
+    /// This is only ever done when asked for, writing an archive never compresses its files.
+    ///
+    /// **Note:** Requires the `yaz0_sarc` feature, otherwise this returns
+    /// [`Error::UnsupportedCompression`](Error::UnsupportedCompression) if any file needs
+    /// compressing.
+    pub fn compress_entries_in_place(&mut self) -> Result<usize, Error> {
+        let mut compressed = 0;
+        for file in &mut self.files {
+            let marked = file.extension().is_some_and(|ext| YAZ0_EXTENSIONS.contains(&ext));
+            if marked && !file.is_compressed() {
+                file.data = compress(&file.data, Compression::Yaz0)?;
+                compressed += 1;
+            }
+        }
 
-        Sfat {
-            entries: self.get_sfat_entries(string_offsets, data_offsets)
-        }.write_options(f, options)?;
+        Ok(compressed)
+    }
 
-        // SFNT Header
-        (
-            b"SFNT",
-            SFNT_HEADER_SIZE as u16,
-            u16::default()
-        ).write_options(f, options)?;
-        
-        string_section.write_options(f, options)?;
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write). This writes the SARC with no 
+    /// compression.
+    ///
+    /// The SFAT is always written in hash order, as the format requires, and the string table and
+    /// data section follow that same order. Files with the same hash are ordered by name, with
+    /// nameless files first, and then by their position in `files`, so the output doesn't depend
+    /// on the order files were added unless they share both hash and name. If `files` is already
+    /// in that order (as it is for a freshly read archive) no sorting is done at all.
+    pub fn write<W: Write>(&self, f: &mut W) -> Result<(), Error> {
+        self.write_padded(f, Alignment::NONE)
+    }
 
-        vec![0u8; data_padding].write_options(f, options)?;
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write), padding the end of the
+    /// archive with zeroes so its total size is a multiple of `alignment`. The padding is counted
+    /// in the file size stored in the header. This writes the SARC with no compression.
+    pub fn write_padded<W: Write>(&self, f: &mut W, alignment: Alignment) -> Result<(), Error> {
+        self.write_with_options(f, &WriteOptions::new().file_alignment(alignment))
+    }
 
-        data_section.write_options(f, options)?;
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write), aligning the start of
+    /// the data section to `alignment` instead of [`Alignment::DATA`](Alignment::DATA). Use
+    /// [`Alignment::data_offset_for_platform`](Alignment::data_offset_for_platform) to match the
+    /// layout a given console's archives use. This writes the SARC with no compression.
+    pub fn write_with_data_alignment<W: Write>(&self, f: &mut W, alignment: Alignment)
+        -> Result<(), Error>
+    {
+        self.write_with_options(f, &WriteOptions::new().data_alignment(alignment))
+    }
 
-        f.flush()
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write), with the names in the
+    /// string table in the given order. Use [`StringOrder::Files`](StringOrder::Files) to
+    /// round-trip archives whose string table isn't sorted by hash. This writes the SARC with no
+    /// compression.
+    pub fn write_with_string_order<W: Write>(&self, f: &mut W, order: StringOrder)
+        -> Result<(), Error>
+    {
+        self.write_with_options(f, &WriteOptions::new().string_order(order))
     }
 
-    fn get_sfat_entries(&self, string_offsets: HashMap<u32, u32>, data_offsets: HashMap<u32, (u32, u32)>)
-        -> Vec<SfatEntry<'_>>
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write), aligning each name in
+    /// the string table to `alignment` instead of [`Alignment::STRING`](Alignment::STRING).
+    /// Alignments below 4 are raised to 4, since the SFAT stores name offsets divided by 4. This
+    /// writes the SARC with no compression.
+    pub fn write_with_string_alignment<W: Write>(&self, f: &mut W, alignment: Alignment)
+        -> Result<(), Error>
     {
-        let mut sfat_entries: Vec<SfatEntry<'_>> = self.files
-            .iter()
-            .map(|file| {
-                let name: Option<&str> = file.name.as_deref();
-                SfatEntry {
-                    name,
-                    name_table_offset:
-                        name.map(sfat_hash)
-                            .and_then(|hash| string_offsets.get(&hash).copied()),
-                    file_range: data_offsets[&name.as_deref().map(sfat_hash).unwrap_or_default()]
-                }
+        self.write_with_options(f, &WriteOptions::new().string_alignment(alignment))
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with as little padding
+    /// as the format allows, aligning the data section and each file without an
+    /// [`alignment`](SarcEntry::alignment) to [`Alignment::MIN_DATA`](Alignment::MIN_DATA). This
+    /// keeps small archives, such as those used in tests, from being padded out to 8 KiB, but
+    /// games may not accept the result. This writes the SARC with no compression.
+    pub fn write_minimal<W: Write>(&self, f: &mut W) -> Result<(), Error> {
+        self.write_with_options(
+            f,
+            &WriteOptions::new().data_alignment(Alignment::MIN_DATA).minimal_padding(true)
+        )
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) without any names,
+    /// leaving the string table empty. Files are still placed and found by the hash of their name,
+    /// so games can still load them, but the names can't be recovered when reading. This writes
+    /// the SARC with no compression.
+    pub fn write_nameless<W: Write>(&self, f: &mut W) -> Result<(), Error> {
+        self.write_with_options(f, &WriteOptions::new().strip_names(true))
+    }
+
+    /// Write to a writer that implements [`std::io::Write`](std::io::Write) with the given
+    /// [`WriteOptions`](WriteOptions). The other write methods are shorthands for this with a
+    /// single option changed. This writes the SARC with no compression.
+    pub fn write_with_options<W: Write>(&self, f: &mut W, options: &WriteOptions)
+        -> Result<(), Error>
+    {
+        let entries = self.entry_refs();
+        write_entries_with(f, self.byte_order, self.version, self.data_alignment, &entries, options)
+    }
+
+    /// Add a file to an uncompressed sarc file on disk without loading the rest of its data into
+    /// memory. The SFAT and string table are rewritten, the existing data section is streamed
+    /// through unchanged, and the new file is placed at the end of it.
+    ///
+    /// This still rewrites the whole file (to a temporary file next to `path` which then replaces
+    /// it), but only ever holds the archive's metadata and the new file in memory. Since the new
+    /// file goes at the end, the data section is no longer in hash order as it would be if written
+    /// by [`write`](SarcFile::write); games don't rely on that order, but the output won't be
+    /// byte-identical to a full rewrite. Compressed archives can't be streamed, so for those use
+    /// [`read_from_file`](SarcFile::read_from_file), add the file, and write the archive again.
+    ///
+    /// The data section keeps the alignment inferred from its current offset (see
+    /// [`SarcEntry::alignment`](SarcEntry::alignment) for how it's inferred). As with
+    /// [`write`](SarcFile::write), the SFAT is always written with [`HASH_KEY`](crate::HASH_KEY), so
+    /// named files are rehashed with it if the archive used a different key.
+    pub fn append_file_to<P: AsRef<Path>>(path: P, name: &str, data: &[u8]) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut old = std::io::BufReader::new(std::fs::File::open(path)?);
+        let crate::parser::Metadata { byte_order, version, data_alignment, data_len, mut files } =
+            crate::parser::read_metadata(&mut old).map_err(Error::ReadError)?;
+        if files.iter().any(|(file, _, _)| file.as_deref() == Some(name.as_bytes())) {
+            return Err(Error::DuplicateName(name.into()));
+        }
+
+        for (name, hash, _) in &mut files {
+            if let Some(name) = name {
+                *hash = sfat_hash_bytes(name);
+            }
+        }
+
+        let start = Alignment::DATA.align(data_len);
+        files.push((Some(name.as_bytes().to_vec()), sfat_hash(name), start..start + data.len()));
+        check_file_count(files.len())?;
+        sort_by_hash(&mut files, |(name, hash, _)| (*hash, name.as_deref()));
+
+        let (string_offsets, string_section) = string_section(
+            files.iter().map(|(name, hash, _)| (*hash, name.as_deref())).collect(),
+            StringOrder::Hash,
+            Alignment::STRING
+        );
+        let data_offset = data_offset(files.len(), string_section.len(), data_alignment);
+        let file_size = checked_file_size(data_offset, start + data.len())?;
+        let mut entries: Vec<_> = files.iter()
+            .zip(string_offsets)
+            .map(|((_, hash, range), name_table_offset)| {
+                SfatEntry::new(*hash, name_table_offset, (range.start as u32, range.end as u32))
             })
             .collect();
-        sfat_entries.sort_by_key(|e| e.name.map(sfat_hash).unwrap_or(0));
-        sfat_entries
+        number_collisions(&mut entries);
+
+        // `old` moves into the closure so it's closed before the temporary file replaces `path`
+        write_atomic(path, move |out| {
+            write_metadata(out, byte_order, version, file_size, data_offset, entries,
+                           &string_section)?;
+
+            let copied = std::io::copy(&mut old.by_ref().take(data_len as u64), out)?;
+            if copied != data_len as u64 {
+                return Err(Error::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            out.write_all(&vec![0u8; start - data_len])?;
+            out.write_all(data)?;
+            Ok(())
+        })
     }
 
-    fn generate_string_section(&self) -> (HashMap<u32, u32>, Vec<u8>) {
-        let mut names: Vec<&str> =
-            self.files.iter().filter_map(|a| Some(a.name.as_ref()?.as_str())).collect();
+    /// Get the size in bytes of the uncompressed archive as it would be written by
+    /// [`write`](SarcFile::write), without actually serializing it.
+    pub fn serialized_size(&self) -> usize {
+        let files = self.entry_refs();
+        let (_, data_section_len) = data_layout(&files, &WriteOptions::default());
+        let data_offset = self.data_alignment.align(metadata_size(&files));
 
-        let mut string_section = vec![];
-        names.sort_by_key(|name| sfat_hash(name));
-        let offsets =
-            names
-                .into_iter()
-                .filter_map(|string| {
-                    let off = string_section.len() as u32;
-                    SarcString::from(string)
-                        .write(&mut string_section)
-                        .ok()?;
-                    Some((sfat_hash(string), off))
-                })
-                .collect();
+        data_offset + data_section_len
+    }
 
-        (offsets, string_section)
+    /// Get the number of bytes of alignment padding the archive would contain if written by
+    /// [`write`](SarcFile::write). This is everything that isn't file data or the archive's
+    /// headers, SFAT, or string table.
+    pub fn padding_overhead(&self) -> usize {
+        self.serialized_size() - self.total_data_size() - metadata_size(&self.entry_refs())
     }
 
-    fn generate_data_section(&self) -> (HashMap<u32, (u32, u32)>, Vec<u8>) {
-        let mut data = vec![];
-        let mut files: Vec<_> = self.files.iter()
-            .map(|file| (file.name.as_deref().map(sfat_hash).unwrap_or_default(), &file.data[..]))
-            .collect();
-        files.sort_by_key(|(hash, _)| *hash);
-        (
-            files.into_iter()
-                .map(|(hash, file)| {
-                    let start_padding = data.len();
-                    let start = (start_padding + 0x1fff) & !0x1fff;
-                    let padding = start - start_padding;
-                    let start = start as u32;
-                    vec![0u8; padding].write(&mut data).unwrap();
-                    file.write(&mut data).unwrap();
-                    let end = data.len() as u32;
-                    (hash, (start, end))
-                })
-                .collect(),
-            data
-        )
+    /// Get where each file's data would be placed by [`write`](SarcFile::write), for finding out
+    /// where alignment padding goes and why an archive is larger than expected. Each file is
+    /// reported as `(name, start, end, padding_before)`, in the order the data is laid out:
+    ///
+    /// * `name` is the file's name, with any invalid UTF-8 replaced by U+FFFD, or `None` for
+    ///   nameless files
+    /// * `start` and `end` are the range of its data relative to the start of the data section,
+    ///   as stored in the SFAT
+    /// * `padding_before` is the number of bytes of padding between the end of the previous
+    ///   file's data (or the start of the data section) and `start`
+    pub fn layout_report(&self) -> Vec<(Option<String>, u32, u32, usize)> {
+        let files = self.entry_refs();
+        let (layout, _) = data_layout(&files, &WriteOptions::default());
+
+        let mut previous_end = 0;
+        layout.iter()
+            .map(|DataLayout { index, range, .. }| {
+                let name = files[*index].name_bytes()
+                    .map(|name| String::from_utf8_lossy(name).into_owned());
+                let padding_before = range.start - previous_end;
+                previous_end = range.end;
+                (name, range.start as u32, range.end as u32, padding_before)
+            })
+            .collect()
+    }
+
+    fn entry_refs(&self) -> Vec<SarcEntryRef<'_>> {
+        self.files.iter().map(SarcEntry::as_entry_ref).collect()
     }
 }
 
-fn magic<B1: BinWrite + Copy, B2: BinWrite>(magic: B1) -> impl Fn(B2) -> (B1, B2) {
-    move |val| (magic, val)
+/// Write an archive made of borrowed files to a writer that implements
+/// [`std::io::Write`](std::io::Write), with the given [`WriteOptions`](WriteOptions). This is the
+/// same as [`SarcFile::write_with_options`](SarcFile::write_with_options), but without having to
+/// copy every name and file into a [`SarcFile`](SarcFile) first. This writes the SARC with no
+/// compression.
+pub fn write_entries<W: Write>(
+    f: &mut W,
+    byte_order: Endian,
+    entries: &[SarcEntryRef],
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    write_entries_with(f, byte_order, SARC_VERSION, Alignment::DATA, entries, options)
 }
 
-fn after<B1: BinWrite + Copy, B2: BinWrite>(after: B1) -> impl Fn(B2) -> (B2, B1) {
-    move |val| (val, after)
+/// Write an archive, aligning the data section to `data_alignment` unless `options` sets one
+fn write_entries_with<W: Write>(
+    f: &mut W,
+    byte_order: Endian,
+    version: u16,
+    data_alignment: Alignment,
+    files: &[SarcEntryRef],
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    check_file_count(files.len())?;
+    if !options.allow_duplicate_names {
+        check_duplicate_names(files)?;
+    }
+
+    let (string_offsets, string_section) = if options.strip_names {
+        (vec![None; files.len()], vec![])
+    } else {
+        generate_string_section(files, options.string_order, options.string_alignment)
+    };
+    let (layout, data_len) = data_layout(files, options);
+
+    let data_alignment = options.data_alignment.unwrap_or(data_alignment);
+    let data_offset = data_offset(files.len(), string_section.len(), data_alignment);
+    let end_padding = options.file_alignment.align(data_offset + data_len)
+        - (data_offset + data_len);
+    let file_size = checked_file_size(data_offset, data_len + end_padding)?;
+
+    write_metadata(
+        f,
+        byte_order,
+        version,
+        file_size,
+        data_offset,
+        get_sfat_entries(files, string_offsets, data_ranges(files.len(), &layout)),
+        &string_section
+    )?;
+
+    write_data_section(f, &layout, data_len)?;
+
+    write_zeros(f, end_padding)?;
+
+    f.flush()?;
+
+    Ok(())
 }
 
-#[derive(BinWrite)]
-struct SarcHeader {
-    #[binwrite(preprocessor(
-        magic((b"SARC", Self::SIZE as u16, Self::BOM))
-    ))]
-    file_size: u32,
-    #[binwrite(postprocessor(after(0x0100u16)), pad_after(2))]
-    data_offset: u32,
+/// Size of everything before the data section: the SARC header, SFAT and SFNT
+fn metadata_size(files: &[SarcEntryRef]) -> usize {
+    let string_section_len: usize = files
+        .iter()
+        .filter_map(|file| file.name_bytes())
+        .map(|name| Alignment::STRING.align(name.len() + 1))
+        .sum();
+
+    SarcHeader::SIZE + Sfat::HEADER_SIZE + (files.len() * SfatEntry::SIZE)
+        + SfntHeader::SIZE + string_section_len
+}
+
+fn check_duplicate_names(files: &[SarcEntryRef]) -> Result<(), Error> {
+    let mut names = std::collections::HashSet::new();
+    for name in files.iter().filter_map(|file| file.name_bytes()) {
+        if !names.insert(name) {
+            return Err(Error::DuplicateName(String::from_utf8_lossy(name).into_owned()));
+        }
+    }
+
+    Ok(())
 }
 
-impl SarcHeader {
-    const SIZE: usize = 0x14;
-    const BOM: u16 = 0xFEFF;
+fn get_sfat_entries(
+    files: &[SarcEntryRef],
+    string_offsets: Vec<Option<u32>>,
+    data_offsets: Vec<(u32, u32)>,
+) -> Vec<SfatEntry> {
+    let mut sfat_entries: Vec<(&SarcEntryRef, SfatEntry)> = files
+        .iter()
+        .zip(string_offsets)
+        .zip(data_offsets)
+        .map(|((file, name_table_offset), file_range)| {
+            (file, SfatEntry::new(file.sfat_hash(), name_table_offset, file_range))
+        })
+        .collect();
+    sort_by_hash(&mut sfat_entries, |(file, e)| (e.hash, file.name_bytes()));
+
+    let mut sfat_entries: Vec<_> = sfat_entries.into_iter().map(|(_, e)| e).collect();
+    number_collisions(&mut sfat_entries);
+    sfat_entries
 }
 
-#[derive(BinWrite, Clone)]
-struct SfatEntry<'a>{
-    #[binwrite(preprocessor(|name: &Option<&str>| name.map(sfat_hash).unwrap_or(0)))]
-    name: Option<&'a str>,
-    
-    #[binwrite(preprocessor(|a| {
-        if let &Some(a) = a {
-            (a / 4) | 0x01000000
-        } else {
-            0
+fn generate_string_section(files: &[SarcEntryRef], order: StringOrder, alignment: Alignment)
+    -> (Vec<Option<u32>>, Vec<u8>)
+{
+    string_section(
+        files.iter().map(|file| (file.sfat_hash(), file.name_bytes())).collect(),
+        order,
+        alignment
+    )
+}
+
+/// Compute where each file's data will be placed within the data section, sorted by hash and
+/// then by the data order of `options`, along with the total length of the data section.
+fn data_layout<'a>(files: &[SarcEntryRef<'a>], options: &WriteOptions)
+    -> (Vec<DataLayout<'a>>, usize)
+{
+    let mut sorted: Vec<_> = files.iter()
+        .enumerate()
+        .map(|(index, file)| (index, file.sfat_hash(), options.alignment_of(file), file.data))
+        .collect();
+    match options.data_order {
+        DataOrder::Name => {
+            sort_by_hash(&mut sorted, |&(index, hash, _, _)| (hash, files[index].name_bytes()))
         }
-    } ))]
-    name_table_offset: Option<u32>,
+        DataOrder::Files => sorted.sort_by_key(|&(_, hash, _, _)| hash),
+        DataOrder::Size => sorted.sort_by_key(|&(_, hash, _, data)| (hash, data.len())),
+    }
 
-    file_range: (u32, u32)
+    let mut end = 0;
+    let layout = sorted.into_iter()
+        .map(|(index, _, alignment, data)| {
+            let start = alignment.align(end);
+            end = start + data.len();
+            trace!(index, range = ?(start..end), "placed file data");
+            DataLayout { index, range: start..end, data }
+        })
+        .collect();
+    debug!(file_count = files.len(), len = end, "laid out data section");
+
+    (layout, end)
 }
 
-impl<'a> SfatEntry<'a> {
-    const SIZE: usize = 0x10;
+/// Get the range of each file's data from its layout, in the same order as `files`. Ranges are
+/// tracked per file rather than per hash so that files sharing a hash, such as several nameless
+/// files, each keep their own data.
+fn data_ranges(num_files: usize, layout: &[DataLayout]) -> Vec<(u32, u32)> {
+    let mut ranges = vec![(0, 0); num_files];
+    for DataLayout { index, range, .. } in layout {
+        ranges[*index] = (range.start as u32, range.end as u32);
+    }
+
+    ranges
 }
 
-fn sfat_header<'a>(entries: &'a Vec<SfatEntry>) -> impl BinWrite + 'a {
-    (
-        b"SFAT",
-        Sfat::HEADER_SIZE as u16,
-        entries.len() as u16,
-        Sfat::HASH_KEY,
-        entries
+/// Build a string table from the `(hash, name)` of each file with each name aligned to
+/// `alignment`, returning it alongside the offset of each file's name in the same order as the
+/// files. Offsets are tracked per file rather than per hash so that files whose names collide each
+/// keep their own name. Alignments below [`Alignment::STRING`](Alignment::STRING) are raised to it,
+/// since the SFAT stores name offsets divided by 4.
+fn string_section(files: Vec<(u32, Option<&[u8]>)>, order: StringOrder, alignment: Alignment)
+    -> (Vec<Option<u32>>, Vec<u8>)
+{
+    let alignment = alignment.max(Alignment::STRING);
+    let mut names: Vec<(usize, u32, &[u8])> = files.iter()
+        .enumerate()
+        .filter_map(|(index, &(hash, name))| Some((index, hash, name?)))
+        .collect();
+    if order == StringOrder::Hash {
+        sort_by_hash(&mut names, |&(_, hash, name)| (hash, Some(name)));
+    }
+
+    let mut string_section = vec![];
+    let mut offsets = vec![None; files.len()];
+    for (index, _, string) in names {
+        offsets[index] = Some(string_section.len() as u32);
+        string_section.extend_from_slice(string);
+        string_section.push(0);
+        string_section.resize(alignment.align(string_section.len()), 0);
+    }
+
+    (offsets, string_section)
+}
+
+/// Get the offset of the data section of an archive with the given number of files and length of
+/// string table, aligned to `alignment`
+fn data_offset(num_files: usize, string_section_len: usize, alignment: Alignment) -> usize {
+    alignment.align(
+        SarcHeader::SIZE + Sfat::HEADER_SIZE + (num_files * SfatEntry::SIZE)
+            + SfntHeader::SIZE + string_section_len
     )
 }
 
-#[derive(BinWrite)]
-struct Sfat<'a> {
-    #[binwrite(preprocessor(sfat_header))]
-    entries: Vec<SfatEntry<'a>>
+/// Write everything in front of the data section: the SARC header, SFAT, SFNT and the padding
+/// before the data section
+fn write_metadata<W: Write>(
+    f: &mut W,
+    byte_order: Endian,
+    version: u16,
+    file_size: u32,
+    data_offset: usize,
+    entries: Vec<SfatEntry>,
+    string_section: &[u8],
+) -> Result<(), Error> {
+    let data_padding_offset = SarcHeader::SIZE + Sfat::HEADER_SIZE
+        + (entries.len() * SfatEntry::SIZE) + SfntHeader::SIZE + string_section.len();
+    let data_padding = data_offset - data_padding_offset;
+
+    let options = &writer_option_new!(endian: byte_order.into());
+
+    SarcHeader {
+        file_size,
+        data_offset: data_offset as u32,
+        version
+    }.write_options(f, options)?;
+
+    Sfat {
+        entries
+    }.write_options(f, options)?;
+
+    SfntHeader.write_options(f, options)?;
+
+    f.write_all(string_section)?;
+
+    f.write_all(&vec![0u8; data_padding])?;
+
+    Ok(())
+}
+
+/// Write `sarc` to a new `Vec<u8>` with no compression. The same as
+/// [`SarcFile::write_to_vec`](SarcFile::write_to_vec), as the counterpart of
+/// [`read_bytes`](crate::read_bytes) for bindings that only pass bytes in and out.
+pub fn write_bytes(sarc: &SarcFile) -> Result<Vec<u8>, Error> {
+    sarc.write_to_vec()
 }
 
-impl<'a> Sfat<'a> {
-    const HEADER_SIZE: usize = 0xC;
-    const HASH_KEY: u32 = 0x00000065;
+/// Compress arbitrary data, such as a nested file before adding it to an archive, with the given
+/// compression. [`Compression::None`](Compression::None) returns the data unchanged.
+///
+/// **Note:** Compression requires the `yaz0_sarc` and/or the `zstd_sarc` features. If the feature
+/// for `compression` isn't enabled this returns
+/// [`Error::UnsupportedCompression`](Error::UnsupportedCompression).
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    compress_to(&mut out, data, compression)?;
+    Ok(out)
+}
+
+/// Extensions of files that are yaz0 compressed by convention, as matched by
+/// [`SarcFile::compress_entries_in_place`](SarcFile::compress_entries_in_place)
+pub const YAZ0_EXTENSIONS: &[&str] = &[
+    "szs", "sbactorpack", "sbeventpack", "sbfres", "sbitemico", "sblarc", "sbmapopen", "sbmaptex",
+    "sbmodelsh", "sbquestpack", "sbreviewtex", "sbstftex", "sbyml", "sstats", "sstera",
+];
+
+/// The yaz0 compression level used for all writes
+#[cfg(feature = "yaz0_sarc")]
+const YAZ0_LEVEL: yaz0::CompressionLevel = yaz0::CompressionLevel::Lookahead { quality: 10 };
+
+/// Compress `data` with the given compression, writing the result to `f`
+fn compress_to<W: Write>(f: &mut W, data: &[u8], compression: Compression) -> Result<(), Error> {
+    match compression {
+        Compression::None => f.write_all(data)?,
+        #[cfg(feature = "yaz0_sarc")]
+        Compression::Yaz0 => {
+            yaz0::Yaz0Writer::new(f)
+                .compress_and_write(data, YAZ0_LEVEL)
+                .map_err(Error::Yaz0Error)?
+        }
+        #[cfg(feature = "zstd_sarc")]
+        Compression::Zstd => {
+            zstd::stream::copy_encode(data, f, zstd::DEFAULT_COMPRESSION_LEVEL)?
+        }
+        compression => return Err(Error::UnsupportedCompression(compression)),
+    }
+
+    Ok(())
 }
 
-#[derive(BinWrite)]
-struct SarcString<'a> {
-    #[binwrite(cstr, align_after(4))]
-    inner: &'a str
+/// Settings for writing an archive with [`write_with_options`](SarcFile::write_with_options).
+/// Every option starts at the value [`write`](SarcFile::write) uses and can be changed with the
+/// method of the same name.
+///
+/// ```rust
+/// use sarc::{Alignment, SarcFile, writer::{StringOrder, WriteOptions}};
+///
+/// let options = WriteOptions::new()
+///     .data_alignment(Alignment::new(0x100).unwrap())
+///     .string_order(StringOrder::Files);
+///
+/// let mut data = vec![];
+/// SarcFile::default().write_with_options(&mut data, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    file_alignment: Alignment,
+    data_alignment: Option<Alignment>,
+    string_order: StringOrder,
+    data_order: DataOrder,
+    string_alignment: Alignment,
+    strip_names: bool,
+    minimal_padding: bool,
+    allow_duplicate_names: bool,
+    alignment_table: HashMap<String, Alignment>,
+    guess_alignment: bool,
 }
 
-impl<'b> SarcString<'b> {
-    fn from(inner: &'b str) -> Self {
+impl Default for WriteOptions {
+    fn default() -> Self {
         Self {
-            inner 
+            file_alignment: Alignment::NONE,
+            data_alignment: None,
+            string_order: StringOrder::Hash,
+            data_order: DataOrder::Name,
+            string_alignment: Alignment::STRING,
+            strip_names: false,
+            minimal_padding: false,
+            allow_duplicate_names: false,
+            alignment_table: HashMap::new(),
+            guess_alignment: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Create options matching [`write`](SarcFile::write)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pad the end of the archive with zeroes so its total size is a multiple of `alignment`. See
+    /// [`write_padded`](SarcFile::write_padded). Defaults to [`Alignment::NONE`](Alignment::NONE).
+    pub fn file_alignment(mut self, alignment: Alignment) -> Self {
+        self.file_alignment = alignment;
+        self
+    }
+
+    /// Align the start of the data section to `alignment`. See
+    /// [`write_with_data_alignment`](SarcFile::write_with_data_alignment). Defaults to the
+    /// archive's own [`data_alignment`](SarcFile::data_alignment), which is
+    /// [`Alignment::DATA`](Alignment::DATA) unless set otherwise.
+    pub fn data_alignment(mut self, alignment: Alignment) -> Self {
+        self.data_alignment = Some(alignment);
+        self
+    }
+
+    /// Order of the names in the string table. See
+    /// [`write_with_string_order`](SarcFile::write_with_string_order). Defaults to
+    /// [`StringOrder::Hash`](StringOrder::Hash).
+    pub fn string_order(mut self, order: StringOrder) -> Self {
+        self.string_order = order;
+        self
+    }
+
+    /// Order of files with the same hash in the data section. Defaults to
+    /// [`DataOrder::Name`](DataOrder::Name).
+    pub fn data_order(mut self, order: DataOrder) -> Self {
+        self.data_order = order;
+        self
+    }
+
+    /// Align each name in the string table to `alignment`, raised to at least 4. See
+    /// [`write_with_string_alignment`](SarcFile::write_with_string_alignment). Defaults to
+    /// [`Alignment::STRING`](Alignment::STRING).
+    pub fn string_alignment(mut self, alignment: Alignment) -> Self {
+        self.string_alignment = alignment;
+        self
+    }
+
+    /// Leave out the string table, writing only hashes. See
+    /// [`write_nameless`](SarcFile::write_nameless). Defaults to `false`.
+    pub fn strip_names(mut self, strip_names: bool) -> Self {
+        self.strip_names = strip_names;
+        self
+    }
+
+    /// Align each file without an [`alignment`](SarcEntry::alignment) to
+    /// [`Alignment::MIN_DATA`](Alignment::MIN_DATA) instead of [`Alignment::DATA`](Alignment::DATA).
+    /// This doesn't change the alignment of the data section itself, so
+    /// [`write_minimal`](SarcFile::write_minimal) also lowers
+    /// [`data_alignment`](WriteOptions::data_alignment). Defaults to `false`.
+    pub fn minimal_padding(mut self, minimal_padding: bool) -> Self {
+        self.minimal_padding = minimal_padding;
+        self
+    }
+
+    /// Write files that share a name instead of returning
+    /// [`Error::DuplicateName`](Error::DuplicateName). Each copy gets its own SFAT node, name and
+    /// data, numbered by the collision count in the SFAT so the archive reads back with every
+    /// copy in the same order. Defaults to `false`.
+    pub fn allow_duplicate_names(mut self, allow_duplicate_names: bool) -> Self {
+        self.allow_duplicate_names = allow_duplicate_names;
+        self
+    }
+
+    /// Align the data of files by their [`extension`](SarcEntry::extension), such as `"bfres"`,
+    /// using a table of the alignments a game requires. Defaults to an empty table.
+    ///
+    /// The alignment of each file is the first of:
+    ///
+    /// 1. its own [`alignment`](SarcEntry::alignment)
+    /// 2. the alignment of its extension in this table
+    /// 3. the [guessed alignment](SarcEntry::guess_alignment) of its data, if
+    ///    [`guess_alignment`](WriteOptions::guess_alignment) is set
+    /// 4. [`Alignment::DATA`](Alignment::DATA), or [`Alignment::MIN_DATA`](Alignment::MIN_DATA)
+    ///    with [`minimal_padding`](WriteOptions::minimal_padding)
+    pub fn alignment_table(mut self, table: HashMap<String, Alignment>) -> Self {
+        self.alignment_table = table;
+        self
+    }
+
+    /// Align the data of files with no alignment of their own or in the
+    /// [`alignment_table`](WriteOptions::alignment_table) to the alignment
+    /// [guessed](SarcEntry::guess_alignment) from their data, where there is one. Defaults to
+    /// `false`.
+    pub fn guess_alignment(mut self, guess_alignment: bool) -> Self {
+        self.guess_alignment = guess_alignment;
+        self
+    }
+
+    /// Get the alignment of a file's data, in the order given by
+    /// [`alignment_table`](WriteOptions::alignment_table)
+    fn alignment_of(&self, file: &SarcEntryRef) -> Alignment {
+        file.alignment
+            .or_else(|| self.alignment_table.get(file.extension()?).copied())
+            .or_else(|| if self.guess_alignment { file.guess_alignment() } else { None })
+            .unwrap_or(if self.minimal_padding { Alignment::MIN_DATA } else { Alignment::DATA })
+    }
+}
+
+/// A writer that counts the bytes written through it
+#[cfg(any(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: usize,
+}
+
+#[cfg(any(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+#[cfg(any(feature = "yaz0_sarc", feature = "zstd_sarc"))]
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that calls `progress(processed, total)` after each write through it
+#[cfg(feature = "zstd_sarc")]
+struct ProgressWriter<W: Write, F: FnMut(usize, usize)> {
+    inner: W,
+    processed: usize,
+    total: usize,
+    progress: F,
+}
+
+#[cfg(feature = "zstd_sarc")]
+impl<W: Write, F: FnMut(usize, usize)> Write for ProgressWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if written != 0 {
+            self.processed += written;
+            (self.progress)(self.processed, self.total);
         }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
     }
 }
 
-const SFNT_HEADER_SIZE: usize = 8;
+/// Sort `items` by hash, breaking ties by name (nameless files first) and then by their original
+/// order, so files with colliding hashes always come out in the same order. The sort is skipped
+/// entirely if `items` are already in that order.
+fn sort_by_hash<T>(items: &mut [T], key: impl Fn(&T) -> (u32, Option<&[u8]>)) {
+    if !items.windows(2).all(|pair| key(&pair[0]) <= key(&pair[1])) {
+        items.sort_by(|a, b| key(a).cmp(&key(b)));
+    }
+    debug_assert!(items.windows(2).all(|pair| key(&pair[0]) <= key(&pair[1])));
+}
+
+/// Counter keeping the temporary files of concurrent writes within this process apart
+static TEMP_FILE_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Create a new temporary file next to `path`, named after it, the process ID and a counter so
+/// that no two writes share one. Never opens a file that already exists.
+fn create_temp_file(path: &Path) -> std::io::Result<(std::ffi::OsString, std::fs::File)> {
+    loop {
+        let mut tmp_path = path.as_os_str().to_owned();
+        let count = TEMP_FILE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        tmp_path.push(format!(".{}.{}.tmp", std::process::id(), count));
+
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&tmp_path) {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Check whether a rename failed because it crossed devices, which is EXDEV on unix and
+/// ERROR_NOT_SAME_DEVICE on windows
+fn crosses_devices(err: &std::io::Error) -> bool {
+    let code = if cfg!(windows) { 17 } else { 18 };
+    cfg!(any(unix, windows)) && err.raw_os_error() == Some(code)
+}
+
+/// Write to `path` through a new temporary file next to it, which replaces `path` once `write`
+/// succeeds and is removed if it fails. The new file keeps the permissions of the file it
+/// replaces, and is synced to disk before the rename. Falls back to copying if the rename crosses
+/// devices.
+fn write_atomic<F>(path: &Path, write: F) -> Result<(), Error>
+    where F: FnOnce(&mut BufWriter<std::fs::File>) -> Result<(), Error>
+{
+    let (tmp_path, file) = create_temp_file(path)?;
+
+    // the temporary file is closed before it's renamed, as windows can't rename open files
+    let written = {
+        let mut out = BufWriter::new(file);
+        write(&mut out).and_then(|()| {
+            let file = out.into_inner().map_err(|err| err.into_error())?;
+            if let Ok(metadata) = std::fs::metadata(path) {
+                file.set_permissions(metadata.permissions())?;
+            }
+            Ok(file.sync_all()?)
+        })
+    };
+    let result = written
+        .and_then(|()| match std::fs::rename(&tmp_path, path) {
+            Err(err) if crosses_devices(&err) => {
+                std::fs::copy(&tmp_path, path)?;
+                std::fs::remove_file(&tmp_path)?;
+                Ok(())
+            }
+            result => Ok(result?),
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Check that `count` files fit in the SFAT, whose header stores the node count as a u16
+pub(crate) fn check_file_count(count: usize) -> Result<(), Error> {
+    if count > Sfat::MAX_NODES {
+        return Err(Error::TooManyFiles(count));
+    }
+    Ok(())
+}
+
+/// Compute the total size of the archive, erroring if it doesn't fit in the header's u32 field
+pub(crate) fn checked_file_size(data_offset: usize, data_len: usize) -> Result<u32, Error> {
+    let file_size = data_offset as u64 + data_len as u64;
+    u32::try_from(file_size).map_err(|_| Error::ArchiveTooLarge(file_size))
+}
+
+/// The placement of a single file's data within the data section
+struct DataLayout<'a> {
+    /// Index of the file in `files`
+    index: usize,
+    range: std::ops::Range<usize>,
+    data: &'a [u8],
+}
+
+/// Write the data section of `size` bytes, streaming each file's data straight from `layout` so
+/// the data section is never held in memory as a whole
+fn write_data_section<W: Write>(f: &mut W, layout: &[DataLayout], size: usize)
+    -> Result<(), Error>
+{
+    let mut end = 0;
+    for file in layout {
+        write_zeros(f, file.range.start - end)?;
+        f.write_all(file.data)?;
+        end = file.range.end;
+    }
+
+    write_zeros(f, size - end)
+}
+
+/// Write `len` zero bytes of padding without allocating a buffer for them
+fn write_zeros<W: Write>(f: &mut W, len: usize) -> Result<(), Error> {
+    std::io::copy(&mut std::io::repeat(0).take(len as u64), f)?;
+    Ok(())
+}
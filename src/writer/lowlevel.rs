@@ -0,0 +1,141 @@
+//! The individual sections of a SARC file as the writer lays them out, for tools writing formats
+//! closely related to SARC that would otherwise have to fork the writer.
+//!
+//! **Note:** This is a lower-level API than the rest of the crate and is not considered stable.
+//! These types follow the writer's internals and may change in a minor release, so pin the
+//! version of this crate if you depend on them. Nothing here checks that the sections it writes
+//! fit together, that's up to the caller.
+//!
+//! Each section implements [`BinWrite`](BinWrite), and is written in the byte order given by the
+//! writer options:
+//!
+//! ```rust
+//! use binwrite::{BinWrite, writer_option_new};
+//! use sarc::writer::lowlevel::{SarcHeader, Sfat, SfatEntry, SfntHeader};
+//!
+//! let mut data = vec![];
+//! let options = writer_option_new!(endian: binwrite::Endian::Little);
+//! SarcHeader { file_size: 0x34, data_offset: 0x34, version: sarc::SARC_VERSION }
+//!     .write_options(&mut data, &options)
+//!     .unwrap();
+//! Sfat { entries: vec![SfatEntry::new(0x1234, None, (0, 0))] }
+//!     .write_options(&mut data, &options)
+//!     .unwrap();
+//! SfntHeader.write_options(&mut data, &options).unwrap();
+//!
+//! assert_eq!(data.len(), SarcHeader::SIZE + Sfat::HEADER_SIZE + SfatEntry::SIZE + SfntHeader::SIZE);
+//! ```
+use binwrite::{BinWrite, WriterOption};
+use std::convert::TryFrom;
+use std::io::Write;
+
+fn magic<B1: BinWrite + Copy, B2: BinWrite>(magic: B1) -> impl Fn(B2) -> (B1, B2) {
+    move |val| (magic, val)
+}
+
+/// The SARC header, written with [`SARC_MAGIC`](crate::SARC_MAGIC) and the byte order mark
+#[derive(BinWrite, Debug, Clone, PartialEq, Eq)]
+pub struct SarcHeader {
+    /// Size of the whole archive, including any padding at the end
+    #[binwrite(preprocessor(
+        magic((&crate::SARC_MAGIC, Self::SIZE as u16, Self::BOM))
+    ))]
+    pub file_size: u32,
+    /// Offset of the data section from the start of the archive
+    pub data_offset: u32,
+    /// Version of the format, [`SARC_VERSION`](crate::SARC_VERSION) for every known archive
+    #[binwrite(pad_after(2))]
+    pub version: u16,
+}
+
+impl SarcHeader {
+    /// Size of the header in bytes
+    pub const SIZE: usize = 0x14;
+    const BOM: u16 = 0xFEFF;
+}
+
+/// A node of the SFAT, describing one file
+#[derive(BinWrite, Debug, Clone, PartialEq, Eq)]
+pub struct SfatEntry {
+    /// The SFAT hash of the file's name
+    pub hash: u32,
+    /// The collision count in the top byte, and the offset of the name divided by 4 in the rest.
+    /// Nameless files have no attributes.
+    pub attributes: u32,
+    /// The start and end of the file's data, relative to the start of the data section
+    pub file_range: (u32, u32)
+}
+
+impl SfatEntry {
+    /// Size of a node in bytes
+    pub const SIZE: usize = 0x10;
+    const COLLISION_COUNT_SHIFT: u32 = 24;
+
+    /// Create a node for a file with the given hash and data range, whose name is at
+    /// `name_table_offset` in the string table, or `None` for a nameless file. The collision count
+    /// is set to 1, use [`number_collisions`](number_collisions) once all nodes are in order.
+    pub fn new(hash: u32, name_table_offset: Option<u32>, file_range: (u32, u32)) -> Self {
+        let attributes = name_table_offset
+            .map(|offset| (offset / 4) | (1 << Self::COLLISION_COUNT_SHIFT))
+            .unwrap_or(0);
+        Self { hash, attributes, file_range }
+    }
+}
+
+/// Number the named files in each run of `entries` sharing a hash with a collision count counting
+/// up from 1 (and saturating at 255), as Nintendo's tools do. `entries` must already be in SFAT
+/// order.
+pub fn number_collisions(entries: &mut [SfatEntry]) {
+    let mut previous_hash = None;
+    let mut count: u32 = 0;
+    for entry in entries.iter_mut().filter(|entry| entry.attributes != 0) {
+        count = if previous_hash == Some(entry.hash) { (count + 1).min(0xFF) } else { 1 };
+        previous_hash = Some(entry.hash);
+        entry.attributes = (entry.attributes & 0x00FF_FFFF)
+            | (count << SfatEntry::COLLISION_COUNT_SHIFT);
+    }
+}
+
+/// The SFAT, written as its header with [`SFAT_MAGIC`](crate::SFAT_MAGIC) and
+/// [`HASH_KEY`](crate::HASH_KEY) followed by its nodes. The nodes must be sorted by hash, and
+/// writing fails with [`InvalidInput`](std::io::ErrorKind::InvalidInput) if there are more than
+/// [`MAX_NODES`](Sfat::MAX_NODES) of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sfat {
+    /// The nodes of the SFAT, one per file
+    pub entries: Vec<SfatEntry>
+}
+
+impl Sfat {
+    /// Size of the SFAT header in bytes, not counting the nodes
+    pub const HEADER_SIZE: usize = 0xC;
+    /// Most nodes the 16-bit node count of the SFAT header can describe
+    pub const MAX_NODES: usize = u16::MAX as usize;
+}
+
+impl BinWrite for Sfat {
+    fn write_options<W: Write>(&self, writer: &mut W, options: &WriterOption) -> std::io::Result<()> {
+        let node_count = u16::try_from(self.entries.len()).map_err(|_| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "too many SFAT nodes for the node count"
+        ))?;
+        (&crate::SFAT_MAGIC, Self::HEADER_SIZE as u16, node_count, crate::HASH_KEY, &self.entries)
+            .write_options(writer, options)
+    }
+}
+
+/// The SFNT header, written with [`SFNT_MAGIC`](crate::SFNT_MAGIC). The string table follows it
+/// directly, as NUL-terminated names each padded to a multiple of 4 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SfntHeader;
+
+impl SfntHeader {
+    /// Size of the header in bytes
+    pub const SIZE: usize = 8;
+}
+
+impl BinWrite for SfntHeader {
+    fn write_options<W: Write>(&self, writer: &mut W, options: &WriterOption) -> std::io::Result<()> {
+        (&crate::SFNT_MAGIC, Self::SIZE as u16, 0u16).write_options(writer, options)
+    }
+}
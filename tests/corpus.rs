@@ -0,0 +1,43 @@
+//! Regression tests for malformed archives. Each file in `tests/corpus/` is a small hand-crafted
+//! archive, see `tests/corpus/README.md` for what each one exercises.
+use sarc::{SarcFile, parser::Error};
+
+type Check = fn(&Result<SarcFile, Error>) -> bool;
+
+const CORPUS: &[(&str, &[u8], Check)] = &[
+    ("valid", include_bytes!("corpus/valid.sarc"), |result| {
+        matches!(result, Ok(sarc) if sarc["a.txt"].data == b"abc")
+    }),
+    ("truncated_header", include_bytes!("corpus/truncated_header.sarc"), |result| {
+        matches!(result, Err(Error::ParseError(_)))
+    }),
+    ("bad_bom", include_bytes!("corpus/bad_bom.sarc"), |result| {
+        matches!(result, Err(Error::BadByteOrderMark(_)))
+    }),
+    ("data_offset_out_of_range", include_bytes!("corpus/data_offset_out_of_range.sarc"), |result| {
+        matches!(result, Err(Error::DataOffsetOutOfBounds(0x1000)))
+    }),
+    ("file_range_out_of_range", include_bytes!("corpus/file_range_out_of_range.sarc"), |result| {
+        matches!(result, Err(Error::ParseError(_)))
+    }),
+    ("truncated_sfat", include_bytes!("corpus/truncated_sfat.sarc"), |result| {
+        matches!(result, Err(Error::TruncatedSfat { declared: 3, available: 2 }))
+    }),
+    ("missing_nul", include_bytes!("corpus/missing_nul.sarc"), |result| {
+        matches!(result, Err(Error::MalformedStringTable))
+    }),
+    ("colliding_hashes", include_bytes!("corpus/colliding_hashes.sarc"), |result| {
+        matches!(
+            result,
+            Ok(sarc) if sarc["itpdcnfu"].data == b"1" && sarc["ntrintqi"].data == b"22"
+        )
+    }),
+];
+
+#[test]
+fn corpus_test() {
+    for (name, data, check) in CORPUS {
+        let result = SarcFile::try_read(data);
+        assert!(check(&result), "{}: unexpected result {:?}", name, result.map(|sarc| sarc.files));
+    }
+}